@@ -0,0 +1,279 @@
+use std::{sync::Mutex, time::Instant};
+
+use image::{ImageBuffer, RgbImage};
+
+use crate::{
+    aov::Features,
+    basic::vec3::Color,
+    config::config,
+    filter::{Splat, SplatTile},
+    tile::Tile,
+    ADAPTIVE_BATCH, ADAPTIVE_ERROR, ADAPTIVE_MAX_RATIO, ADAPTIVE_MIN_SAMPLES, TIME_LIMIT_BATCH,
+};
+
+// Adaptive sampling with --adaptive: every pixel first takes ADAPTIVE_MIN_SAMPLES, then
+// the pixels whose mean is not yet known to ADAPTIVE_ERROR take another batch per pass,
+// until they converge or the budget of `samples_per_pixel` on average runs out.
+// A pixel only looks at its own samples and the budget only at whole passes, so the
+// image does not depend on the threads.
+// In progressive mode no pass takes more than `progressive_spp` samples per pixel.
+// With a time limit every pixel takes one sample, then TIME_LIMIT_BATCH per pass until
+// the deadline, which drops the tiles of the pass not done by then. Every pixel is
+// normalized by its own sample count, so the image is right however far it got.
+
+// running mean and variance of the luminance (Welford), along with the color sum
+#[derive(Clone, Copy, Default)]
+pub struct PixelEstimate {
+    pub sum: Color,
+    pub count: u32,
+    pub mean: f64,
+    pub m2: f64,
+    // the first hits of the samples, for the AOVs and the denoiser
+    pub features: Features,
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, color: Color) {
+        self.sum += color;
+        self.count += 1;
+        let y = color.luminance();
+        let delta = y - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (y - self.mean);
+    }
+    // combines the running statistics of two sets of samples (Chan et al.)
+    pub fn merge(&mut self, other: &PixelEstimate) {
+        let count = self.count + other.count;
+        if count == 0 {
+            return;
+        }
+        let delta = other.mean - self.mean;
+        let weight = self.count as f64 * other.count as f64 / count as f64;
+        self.sum += other.sum;
+        self.features.add(&other.features);
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * weight;
+        self.count = count;
+    }
+    // of the mean luminance, a single sample is taken to spread as far as its value
+    pub fn mean_variance(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            1 => self.mean * self.mean,
+            count => self.m2 / (count - 1) as f64 / count as f64,
+        }
+    }
+    pub fn converged(&self) -> bool {
+        if self.count < ADAPTIVE_MIN_SAMPLES.max(2) {
+            return false;
+        }
+        if self.count >= ADAPTIVE_MAX_RATIO * config().samples_per_pixel {
+            return true;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        let standard_error = (variance / self.count as f64).sqrt();
+        // dark pixels are judged against a floor, or black ones would sample forever
+        standard_error <= ADAPTIVE_ERROR * self.mean.max(0.01)
+    }
+    // in the layout of `write_color`, which averages over the samples per pixel
+    pub fn value(&self) -> Color {
+        if self.count == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.sum * (config().samples_per_pixel as f64 / self.count as f64)
+    }
+}
+
+fn progressive(batch: u32) -> u32 {
+    match config().progressive_spp {
+        Some(spp) => batch.min(spp),
+        None => batch,
+    }
+}
+
+// the framebuffer of main's tile renderer, one pass over the image at a time
+pub struct AdaptiveFilm {
+    pub pass: u32,
+    // samples every unfinished pixel takes this pass, 0 when the render is done
+    pub batch: u32,
+    // finished tiles are written back here, one lock per line
+    pub lines: Vec<Mutex<Vec<PixelEstimate>>>,
+    pub tiles: Vec<Tile>,
+    // the tiles of this pass written back so far, by their index in `tiles`
+    pub done: Mutex<Vec<bool>>,
+    // the reconstruction filter splats of every tile, written back along with it
+    pub splats: Vec<Mutex<SplatTile>>,
+    // of the time limit, set by the renderer
+    pub deadline: Option<Instant>,
+}
+
+impl AdaptiveFilm {
+    pub fn new() -> Self {
+        let tiles = config().tile_order.tiles();
+        AdaptiveFilm {
+            pass: 0,
+            batch: progressive(if config().time_limit.is_some() {
+                1
+            } else if config().adaptive {
+                ADAPTIVE_MIN_SAMPLES.min(config().samples_per_pixel)
+            } else {
+                config().samples_per_pixel
+            }),
+            lines: (0..config().image_height)
+                .map(|_| {
+                    Mutex::new(vec![
+                        PixelEstimate::default();
+                        config().image_width as usize
+                    ])
+                })
+                .collect(),
+            done: Mutex::new(vec![false; tiles.len()]),
+            splats: tiles
+                .iter()
+                .map(|tile| Mutex::new(SplatTile::new(tile)))
+                .collect(),
+            tiles,
+            deadline: None,
+        }
+    }
+
+    // the first pass always completes, so every pixel has a sample
+    pub fn time_up(&self) -> bool {
+        self.pass > 0
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // called between passes: hands out the rest of the budget
+    pub fn next_pass(&mut self) {
+        self.pass += 1;
+        self.done.get_mut().unwrap().fill(false);
+        if config().time_limit.is_some() {
+            self.batch = progressive(TIME_LIMIT_BATCH);
+            return;
+        }
+
+        // pixels without samples belong to other distributed workers
+        let mut used = 0_u64;
+        let mut rendered = 0_u64;
+        let mut unfinished = 0_u64;
+        let mut max_count = 0;
+        for line in self.lines.iter() {
+            for pixel in line.lock().unwrap().iter().filter(|pixel| pixel.count > 0) {
+                used += pixel.count as u64;
+                rendered += 1;
+                max_count = max_count.max(pixel.count);
+                if !pixel.converged() {
+                    unfinished += 1;
+                }
+            }
+        }
+        if !config().adaptive {
+            // every pixel has taken the same samples
+            self.batch = progressive(config().samples_per_pixel.saturating_sub(max_count));
+            return;
+        }
+
+        let budget = config().samples_per_pixel as u64 * rendered;
+        self.batch = if unfinished == 0 || used >= budget {
+            0
+        } else {
+            progressive(((budget - used) / unfinished).min(ADAPTIVE_BATCH as u64) as u32)
+        };
+    }
+
+    // adds the samples of another film, e.g. of a distributed worker
+    pub fn merge(&mut self, other: AdaptiveFilm) {
+        for (line, other_line) in self.lines.iter_mut().zip(other.lines) {
+            let pixels = line.get_mut().unwrap().iter_mut();
+            for (pixel, other_pixel) in pixels.zip(other_line.into_inner().unwrap()) {
+                pixel.merge(&other_pixel);
+            }
+        }
+        for (tile, other_tile) in self.splats.iter_mut().zip(other.splats) {
+            let splats = tile.get_mut().unwrap().splats.iter_mut();
+            for (splat, other_splat) in splats.zip(other_tile.into_inner().unwrap().splats) {
+                splat.sum += other_splat.sum;
+                splat.weight += other_splat.weight;
+            }
+        }
+    }
+
+    // the filtered colors in the layout of `write_color`
+    pub fn colors(&self) -> Vec<(u32, Vec<Color>)> {
+        let width = config().image_width as usize;
+        let mut splats = vec![Splat::default(); width * config().image_height as usize];
+        for tile in self.splats.iter() {
+            tile.lock().unwrap().add_to(&mut splats);
+        }
+        let spp = config().samples_per_pixel as f64;
+        self.lines
+            .iter()
+            .zip(splats.chunks(width))
+            .enumerate()
+            .map(|(py, (line, splats))| {
+                let line = line.lock().unwrap();
+                let colors = line.iter().zip(splats).map(|(pixel, splat)| {
+                    // negative lobes can cancel out all weight
+                    if splat.weight > 0.0 {
+                        splat.sum * (spp / splat.weight)
+                    } else {
+                        pixel.value()
+                    }
+                });
+                (py as u32, colors.collect())
+            })
+            .collect()
+    }
+
+    fn counts(&self) -> Vec<u32> {
+        self.lines
+            .iter()
+            .flat_map(|line| {
+                line.lock()
+                    .unwrap()
+                    .iter()
+                    .map(|pixel| pixel.count)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // the mean, least and most samples per pixel, of the pixels rendered here
+    pub fn sample_counts(&self) -> (f64, u32, u32) {
+        let counts: Vec<u32> = self.counts().into_iter().filter(|&c| c > 0).collect();
+        let sum: u64 = counts.iter().map(|&c| c as u64).sum();
+        (
+            sum as f64 / counts.len().max(1) as f64,
+            counts.iter().copied().min().unwrap_or(0),
+            counts.iter().copied().max().unwrap_or(0),
+        )
+    }
+
+    // blue for the fewest samples to red for the most, black for the pixels not rendered
+    pub fn heatmap(&self) -> RgbImage {
+        let counts = self.counts();
+        let rendered = || counts.iter().copied().filter(|&count| count > 0);
+        let min_count = rendered().min().unwrap_or(0);
+        let max_count = rendered().max().unwrap_or(0).max(min_count + 1);
+        let mut img: RgbImage = ImageBuffer::new(config().image_width, config().image_height);
+        for (py, line) in self.lines.iter().enumerate() {
+            for (px, pixel) in line.lock().unwrap().iter().enumerate() {
+                if pixel.count == 0 {
+                    continue;
+                }
+                let t = (pixel.count - min_count) as f64 / (max_count - min_count) as f64;
+                let r = (255.0 * (2.0 * t - 1.0).clamp(0.0, 1.0)) as u8;
+                let g = (255.0 * (1.0 - (2.0 * t - 1.0).abs())) as u8;
+                let b = (255.0 * (1.0 - 2.0 * t).clamp(0.0, 1.0)) as u8;
+                img.put_pixel(
+                    px as u32,
+                    config().image_height - py as u32 - 1,
+                    image::Rgb([r, g, b]),
+                );
+            }
+        }
+        img
+    }
+}
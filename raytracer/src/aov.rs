@@ -0,0 +1,327 @@
+use clap::ValueEnum;
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, WritableImage,
+};
+use image::{DynamicImage, ImageBuffer, Rgb};
+use serde::Deserialize;
+
+use crate::{
+    adaptive::AdaptiveFilm,
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
+    config::config,
+    hittable::HitRecord,
+    output::{self, ImageFormat},
+    status_bar::show_output,
+    tonemap,
+};
+
+// Arbitrary output variables: what the camera rays hit first, for compositing and
+// for the denoiser. A multilayer .exr output gets them as further channels, the
+// other formats as images next to it, `<output>_<aov>.<ext>`. The linear formats
+// hold the values, the others a picture of them. All are cropped like the image.
+
+// What the camera ray of a sample hits first, summed per pixel like the radiance.
+// The sky and media have no normal and no depth, their albedo is the background.
+#[derive(Clone, Copy, Default)]
+pub struct Features {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub depth: f64,
+    pub position: Point3,
+    pub u: f64,
+    pub v: f64,
+    // samples that hit a surface, which the position and uv are summed over
+    pub hits: u32,
+    // of the first sample with any, IDs do not average
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl Features {
+    pub fn first_hit(r: &Ray, rec: &Option<HitRecord>, background: Color) -> Self {
+        match rec {
+            // media scatter at random depths, mixed with misses in the same pixel
+            Some(rec) if rec.mat_ptr.is_medium() => Features {
+                albedo: background,
+                object_id: rec.object_id,
+                material_id: rec.mat_ptr.material_id(),
+                ..Features::default()
+            },
+            Some(rec) => Features {
+                albedo: rec.mat_ptr.albedo(rec),
+                normal: rec.normal,
+                depth: rec.t * r.direction().length(),
+                position: rec.p,
+                u: rec.u,
+                v: rec.v,
+                hits: 1,
+                object_id: rec.object_id,
+                material_id: rec.mat_ptr.material_id(),
+            },
+            None => Features {
+                albedo: background,
+                ..Features::default()
+            },
+        }
+    }
+    pub fn add(&mut self, other: &Features) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth += other.depth;
+        self.position += other.position;
+        self.u += other.u;
+        self.v += other.v;
+        self.hits += other.hits;
+        if self.object_id == 0 {
+            self.object_id = other.object_id;
+        }
+        if self.material_id == 0 {
+            self.material_id = other.material_id;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Aov {
+    // distance along the camera ray, 0 for the sky
+    Depth,
+    // shading normal in world space
+    Normal,
+    // world space
+    Position,
+    Uv,
+    // reflectance of the surface, the background for the sky
+    Albedo,
+    // 1, 2, ... for the top-level objects of a scene file, 0 for the sky
+    ObjectId,
+    // 1, 2, ... for the materials of a scene file, 0 for the sky
+    MaterialId,
+}
+
+impl Aov {
+    fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+        }
+    }
+
+    // of the .exr layer, the first ones of `value`
+    fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::ObjectId | Aov::MaterialId => &["ID"],
+        }
+    }
+
+    // the pixel average, single values in all three components
+    fn value(self, features: &Features, count: u32) -> Color {
+        let hits = features.hits.max(1) as f64;
+        match self {
+            Aov::Depth => {
+                let depth = features.depth / hits;
+                Color::new(depth, depth, depth)
+            }
+            Aov::Normal => {
+                if features.normal.length() > 1e-6 {
+                    features.normal.unit_vec()
+                } else {
+                    Vec3::default()
+                }
+            }
+            Aov::Position => features.position / hits,
+            Aov::Uv => Color::new(features.u / hits, features.v / hits, 0.0),
+            Aov::Albedo => features.albedo / count.max(1) as f64,
+            Aov::ObjectId => {
+                let id = features.object_id as f64;
+                Color::new(id, id, id)
+            }
+            Aov::MaterialId => {
+                let id = features.material_id as f64;
+                Color::new(id, id, id)
+            }
+        }
+    }
+
+    // top row first, like the radiance of the HDR formats, cropped
+    fn image(self, film: &AdaptiveFilm) -> Vec<Color> {
+        let values: Vec<Color> = film
+            .lines
+            .iter()
+            .rev()
+            .flat_map(|line| {
+                let line = line.lock().unwrap();
+                line.iter()
+                    .map(|pixel| self.value(&pixel.features, pixel.count))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        output::crop(&values)
+    }
+
+    // to [0, 1] for the LDR formats
+    fn picture(self, values: &[Color], film: &AdaptiveFilm) -> Vec<Color> {
+        let hit: Vec<bool> = film
+            .lines
+            .iter()
+            .rev()
+            .flat_map(|line| {
+                let line = line.lock().unwrap();
+                line.iter()
+                    .map(|pixel| pixel.features.hits > 0)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let hit = output::crop(&hit);
+        let hits = || values.iter().zip(&hit).filter(|(_, &hit)| hit);
+        match self {
+            // near is bright
+            Aov::Depth => {
+                let max = hits().fold(0.0_f64, |max, (value, _)| max.max(value.0));
+                values
+                    .iter()
+                    .zip(&hit)
+                    .map(|(value, &hit)| {
+                        if hit && max > 0.0 {
+                            let shade = 1.0 - 0.9 * value.0 / max;
+                            Color::new(shade, shade, shade)
+                        } else {
+                            Color::default()
+                        }
+                    })
+                    .collect()
+            }
+            Aov::Normal => values
+                .iter()
+                .zip(&hit)
+                .map(|(value, &hit)| {
+                    if hit {
+                        *value * 0.5 + Color::new(0.5, 0.5, 0.5)
+                    } else {
+                        Color::default()
+                    }
+                })
+                .collect(),
+            // the bounding box of the hits to the unit cube
+            Aov::Position => {
+                let mut min = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+                let mut max = -min;
+                for (value, _) in hits() {
+                    for axis in 0..3 {
+                        min[axis] = min[axis].min(value[axis]);
+                        max[axis] = max[axis].max(value[axis]);
+                    }
+                }
+                values
+                    .iter()
+                    .zip(&hit)
+                    .map(|(value, &hit)| {
+                        let mut c = Color::default();
+                        if hit {
+                            for axis in 0..3 {
+                                let extent = max[axis] - min[axis];
+                                if extent > 0.0 {
+                                    c[axis] = (value[axis] - min[axis]) / extent;
+                                }
+                            }
+                        }
+                        c
+                    })
+                    .collect()
+            }
+            Aov::Uv => values.to_vec(),
+            Aov::Albedo => values
+                .iter()
+                .map(|value| {
+                    let encode = |x: f64| tonemap::srgb_encode(x.clamp(0.0, 1.0));
+                    Color::new(encode(value.0), encode(value.1), encode(value.2))
+                })
+                .collect(),
+            Aov::ObjectId | Aov::MaterialId => values
+                .iter()
+                .map(|value| id_color(value.0 as u32))
+                .collect(),
+        }
+    }
+}
+
+// a fixed random color per ID, black for 0
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::default();
+    }
+    let hash = id.wrapping_mul(0x9e37_79b9);
+    let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(24), channel(16), channel(8))
+}
+
+// every AOV as its own image next to `path`, in the format of its extension
+pub fn write_images(path: &str, film: &AdaptiveFilm) {
+    let (stem, extension) = path.rsplit_once('.').unwrap_or((path, "jpg"));
+    let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Jpeg);
+    for &aov in config().aovs.iter() {
+        let aov_path = format!("{}_{}.{}", stem, aov.name(), extension);
+        let values = aov.image(film);
+        let result = if format.is_hdr() {
+            output::write_hdr(&aov_path, format, &values)
+        } else {
+            let picture = aov.picture(&values, film);
+            let (width, height) = output::size();
+            let img = ImageBuffer::from_fn(width, height, |x, y| {
+                let c = picture[(y * width + x) as usize];
+                let byte = |x: f64| (x.clamp(0.0, 0.999) * 256.0).floor() as u8;
+                Rgb([byte(c.0), byte(c.1), byte(c.2)])
+            });
+            output::write_ldr(&aov_path, format, &DynamicImage::ImageRgb8(img))
+        };
+        show_output("image", &aov_path, result);
+    }
+}
+
+// one layer with the cropped RGB radiance and `<aov>.<channel>` for every AOV
+pub fn write_exr(path: &str, radiance: &[Color], film: &AdaptiveFilm) {
+    let (width, height) = output::size();
+    let size = (width as usize, height as usize);
+    let mut channels = vec![
+        AnyChannel::new("R", FlatSamples::F32(component(radiance, 0))),
+        AnyChannel::new("G", FlatSamples::F32(component(radiance, 1))),
+        AnyChannel::new("B", FlatSamples::F32(component(radiance, 2))),
+    ];
+    for &aov in config().aovs.iter() {
+        let values = aov.image(film);
+        for (index, channel) in aov.channels().iter().enumerate() {
+            let name = format!("{}.{}", aov.name(), channel);
+            let samples = match aov {
+                Aov::ObjectId | Aov::MaterialId => {
+                    FlatSamples::U32(values.iter().map(|value| value.0 as u32).collect())
+                }
+                _ => FlatSamples::F32(component(&values, index)),
+            };
+            channels.push(AnyChannel::new(name.as_str(), samples));
+        }
+    }
+    let layer = Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels.into()),
+    );
+    let result = Image::from_layer(layer).write().to_file(path);
+    show_output("image", path, result);
+}
+
+fn component(values: &[Color], index: usize) -> Vec<f32> {
+    values.iter().map(|value| value[index] as f32).collect()
+}
@@ -5,7 +5,6 @@ pub mod vec3;
 
 // Constants
 pub use std::f64::consts::PI;
-pub use std::f64::INFINITY;
 
 use rand::Rng;
 
@@ -19,6 +18,7 @@ pub fn random_double_unit() -> f64 {
     rng.gen::<f64>()
 }
 
+#[allow(dead_code)]
 pub fn random_double(min: f64, max: f64) -> f64 {
     min + (max - min) * random_double_unit()
 }
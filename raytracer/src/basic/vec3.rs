@@ -2,9 +2,13 @@ use std::f64::consts::PI;
 use std::ops::{Add, Div, Index, IndexMut, Mul, Sub};
 use std::ops::{AddAssign, DivAssign, MulAssign, Neg};
 
+use serde::Deserialize;
+
 use super::{random_double, random_double_unit};
+use crate::sampler::Sampler;
 
-#[derive(Debug, Clone, Copy, Default)]
+// [x, y, z] in scene files
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
 pub struct Vec3(pub f64, pub f64, pub f64);
 
 impl Neg for Vec3 {
@@ -122,6 +126,7 @@ impl Vec3 {
             random_double_unit(),
         )
     }
+    #[allow(dead_code)]
     pub fn random(min: f64, max: f64) -> Self {
         Vec3(
             random_double(min, max),
@@ -129,6 +134,12 @@ impl Vec3 {
             random_double(min, max),
         )
     }
+    pub fn max_component(&self) -> f64 {
+        self.0.max(self.1).max(self.2)
+    }
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
     #[allow(dead_code)]
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
@@ -136,32 +147,26 @@ impl Vec3 {
     }
 }
 
-pub fn random_in_unit_sphere() -> Vec3 {
-    let mut p = Vec3::random(-1.0, 1.0);
-    loop {
-        if p.length().powi(2) < 1.0 {
-            break;
-        }
-        p = Vec3::random(-1.0, 1.0);
-    }
-    p
+// The warps below map sampler dimensions directly instead of rejection sampling,
+// so a stratified sampler stays stratified after them.
+
+pub fn random_in_unit_sphere(sampler: &mut dyn Sampler) -> Vec3 {
+    random_unit_vector(sampler) * sampler.get_1d().cbrt()
 }
 
-#[allow(dead_code)]
-pub fn random_unit_vector() -> Vec3 {
-    random_in_unit_sphere().unit_vec()
+pub fn random_unit_vector(sampler: &mut dyn Sampler) -> Vec3 {
+    let (u1, u2) = sampler.get_2d();
+    let z = 1.0 - 2.0 * u1;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3(r * phi.cos(), r * phi.sin(), z)
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
-    let ret: Vec3;
-    loop {
-        let p = Vec3(random_double(-1.0, 1.0), random_double(-1.0, 1.0), 0.0);
-        if p.length() < 1.0 {
-            ret = p;
-            break;
-        }
-    }
-    ret
+pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Vec3 {
+    let (u1, u2) = sampler.get_2d();
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3(r * phi.cos(), r * phi.sin(), 0.0)
 }
 
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
@@ -176,8 +181,8 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
 }
 
 #[allow(dead_code)]
-pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
-    let in_unit_sphere = random_in_unit_sphere();
+pub fn random_in_hemisphere(normal: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+    let in_unit_sphere = random_in_unit_sphere(sampler);
     if dot(&in_unit_sphere, normal) > 0.0 {
         in_unit_sphere
     } else {
@@ -185,9 +190,8 @@ pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
     }
 }
 
-pub fn random_cosine_direction() -> Vec3 {
-    let r1 = random_double_unit();
-    let r2 = random_double_unit();
+pub fn random_cosine_direction(sampler: &mut dyn Sampler) -> Vec3 {
+    let (r1, r2) = sampler.get_2d();
     let z = (1.0 - r2).sqrt();
 
     let phi = 2.0 * PI * r1;
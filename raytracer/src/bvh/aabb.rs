@@ -28,6 +28,10 @@ impl Aabb {
         }
         true
     }
+    // with the slack the flat boxes have
+    pub fn contains(&self, p: &Point3) -> bool {
+        (0..3).all(|a| p[a] >= self.minimum[a] - 0.0001 && p[a] <= self.maximum[a] + 0.0001)
+    }
     pub fn min(&self) -> Point3 {
         self.minimum
     }
@@ -1,10 +1,14 @@
 use std::cmp::Ordering;
 
-use rand::Rng;
-
 use crate::{
-    basic::ray::Ray,
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
+    config::config,
     hittable::{hittable_list::HittableList, HitRecord, Hittable},
+    sampler::hash,
+    stats::{self, Counter},
 };
 
 use self::aabb::{surrounding_box, Aabb};
@@ -19,6 +23,7 @@ pub struct BvhNode {
 
 impl Hittable for BvhNode {
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::BvhNodeTests);
         if !self.box_.hit(r, t_min, t_max) {
             return false;
         }
@@ -42,6 +47,21 @@ impl Hittable for BvhNode {
         *output_box = self.box_;
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        if !self.box_.contains(p) {
+            return Color::default();
+        }
+        let emitted = |node: &Option<Box<dyn Hittable>>| match node {
+            Some(node) => node.emitted_at(p, w),
+            None => Color::default(),
+        };
+        let left = emitted(&self.left);
+        if left.max_component() > 0.0 {
+            left
+        } else {
+            emitted(&self.right)
+        }
+    }
 }
 
 impl BvhNode {
@@ -50,7 +70,8 @@ impl BvhNode {
         time0: f64,
         time1: f64,
     ) -> BvhNode {
-        let axis: u32 = rand::thread_rng().gen_range(0..3);
+        // a seeded axis, so every thread builds the same tree
+        let axis = hash(&[config().seed, src_objects.len() as u64]) % 3;
 
         let comparator = if axis == 0 {
             BvhNode::box_x_compare
@@ -1,7 +1,10 @@
-use crate::basic::{
-    degrees_to_radians, random_double,
-    ray::Ray,
-    vec3::{cross, random_in_unit_disk, Point3, Vec3},
+use crate::{
+    basic::{
+        degrees_to_radians,
+        ray::Ray,
+        vec3::{cross, random_in_unit_disk, Point3, Vec3},
+    },
+    sampler::Sampler,
 };
 
 #[derive(Clone, Copy)]
@@ -62,15 +65,19 @@ impl Camera {
 }
 
 impl Camera {
-    pub fn get_ray(self, s: f64, t: f64) -> Ray {
-        let rd = random_in_unit_disk() * self.lens_radius;
+    pub fn get_ray(self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Ray {
+        let rd = random_in_unit_disk(sampler) * self.lens_radius;
         let offset = self.u * rd.0 + self.v * rd.1;
         Ray {
             orig: self.origin + offset,
             dir: self.lower_left_corner + self.horizontal * s + self.vertical * t
                 - self.origin
                 - offset,
-            tm: random_double(self.time0, self.time1), // shutter open/close times
+            tm: self.random_time(sampler),
         }
     }
+    pub fn random_time(self, sampler: &mut dyn Sampler) -> f64 {
+        // shutter open/close times
+        self.time0 + (self.time1 - self.time0) * sampler.get_1d()
+    }
 }
@@ -0,0 +1,394 @@
+use std::{
+    convert::TryInto,
+    fs, io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use clap::ValueEnum;
+use console::style;
+
+use crate::{
+    adaptive::{AdaptiveFilm, PixelEstimate},
+    aov::Features,
+    basic::vec3::{Color, Vec3},
+    config::config,
+    status_bar::show_resumed,
+    tile::{self, Tile},
+    CHECKPOINT_INTERVAL,
+};
+
+// The state of the tile renderer between two tiles: the pass, which tiles of it
+// are written back already and every pixel estimate, little-endian with the f64
+// bits as they are, so a resumed render continues exactly where it stopped.
+// The settings it was rendered with lead the file, only the same render reads it.
+// Distributed workers hand in their results in the same format.
+
+const MAGIC: &[u8; 8] = b"RTCKPT07";
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// set by Ctrl-C, the tiles being rendered are dropped and redone on resume
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+pub struct Checkpoint {
+    path: String,
+    last_save: Mutex<Instant>,
+}
+
+impl Checkpoint {
+    pub fn new(path: &str) -> Self {
+        ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed))
+            .expect("Cannot set the Ctrl-C handler");
+        Checkpoint {
+            path: path.to_string(),
+            last_save: Mutex::new(Instant::now()),
+        }
+    }
+
+    // called after every tile, saves when CHECKPOINT_INTERVAL has passed
+    pub fn tick(&self, film: &AdaptiveFilm) {
+        // another thread is saving already
+        let mut last_save = match self.last_save.try_lock() {
+            Ok(last_save) => last_save,
+            Err(_) => return,
+        };
+        if last_save.elapsed() >= CHECKPOINT_INTERVAL {
+            self.save(film);
+            *last_save = Instant::now();
+        }
+    }
+
+    pub fn save(&self, film: &AdaptiveFilm) {
+        if let Err(err) = write_film(&self.path, film) {
+            eprintln!(
+                "{} {}: {}",
+                style("Saving checkpoint fails").red(),
+                self.path,
+                err
+            );
+        }
+    }
+}
+
+// the settings a film only continues under, by name
+fn settings() -> [(&'static str, String); 12] {
+    let cfg = config();
+    [
+        ("scene", cfg.scene.clone()),
+        (
+            "image size",
+            format!("{}x{}", cfg.image_width, cfg.image_height),
+        ),
+        ("samples per pixel", cfg.samples_per_pixel.to_string()),
+        ("maximum depth", cfg.max_depth.to_string()),
+        ("integrator", value_name(cfg.integrator)),
+        ("sampler", value_name(cfg.sampler)),
+        ("mis heuristic", value_name(cfg.mis)),
+        ("filter", value_name(cfg.filter)),
+        ("filter radius", cfg.filter_radius.to_string()),
+        ("seed", cfg.seed.to_string()),
+        ("tile order", value_name(cfg.tile_order)),
+        ("adaptive sampling", cfg.adaptive.to_string()),
+    ]
+}
+
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+pub fn write_film(path: &str, film: &AdaptiveFilm) -> io::Result<()> {
+    let mut bytes = MAGIC.to_vec();
+    for (_, value) in settings().iter() {
+        bytes.extend((value.len() as u32).to_le_bytes());
+        bytes.extend(value.as_bytes());
+    }
+    {
+        // holding `done` keeps tiles from being written back meanwhile
+        let done = film.done.lock().unwrap();
+        let window = tile::window();
+        for value in [
+            film.pass,
+            film.batch,
+            done.len() as u32,
+            window.x0,
+            window.y0,
+            window.x1,
+            window.y1,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(done.iter().map(|&tile_done| tile_done as u8));
+        for line in film.lines.iter() {
+            for pixel in line.lock().unwrap().iter() {
+                let features = &pixel.features;
+                for value in [
+                    pixel.sum.0,
+                    pixel.sum.1,
+                    pixel.sum.2,
+                    pixel.mean,
+                    pixel.m2,
+                    features.albedo.0,
+                    features.albedo.1,
+                    features.albedo.2,
+                    features.normal.0,
+                    features.normal.1,
+                    features.normal.2,
+                    features.depth,
+                    features.position.0,
+                    features.position.1,
+                    features.position.2,
+                    features.u,
+                    features.v,
+                ] {
+                    bytes.extend(value.to_le_bytes());
+                }
+                for value in [
+                    pixel.count,
+                    features.hits,
+                    features.object_id,
+                    features.material_id,
+                ] {
+                    bytes.extend(value.to_le_bytes());
+                }
+            }
+        }
+        for tile in film.splats.iter() {
+            for splat in tile.lock().unwrap().splats.iter() {
+                for value in [splat.sum.0, splat.sum.1, splat.sum.2, splat.weight] {
+                    bytes.extend(value.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    // a kill while writing must not destroy the last file
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, &bytes)?;
+    fs::rename(&tmp, path)
+}
+
+// reads a film of this very render
+pub fn read_film(path: &str) -> Result<AdaptiveFilm, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    if !bytes.starts_with(MAGIC) {
+        return Err("not a checkpoint file".to_string());
+    }
+    let mut reader = Reader {
+        bytes: &bytes[MAGIC.len()..],
+    };
+    let truncated = || "file is truncated".to_string();
+    for (name, value) in settings().iter() {
+        let len = reader.u32().ok_or_else(truncated)?;
+        let saved = reader.take(len as usize).ok_or_else(truncated)?;
+        let saved = String::from_utf8_lossy(saved);
+        if saved != *value {
+            return Err(format!(
+                "it was rendered with the {} {}, not {}",
+                name, saved, value
+            ));
+        }
+    }
+    let mut header = [0_u32; 7];
+    for value in header.iter_mut() {
+        *value = reader.u32().ok_or_else(truncated)?;
+    }
+    let [pass, batch, done_num, x0, y0, x1, y1] = header;
+    let mut film = AdaptiveFilm::new();
+    let tile_num = film.tiles.len();
+    if done_num as usize != tile_num {
+        return Err("it was rendered with another tile size".to_string());
+    }
+    if (Tile { x0, y0, x1, y1 }) != tile::window() {
+        return Err("it was rendered with another crop".to_string());
+    }
+
+    film.pass = pass;
+    film.batch = batch;
+    let done = reader.take(tile_num).ok_or_else(truncated)?;
+    *film.done.get_mut().unwrap() = done.iter().map(|&tile_done| tile_done != 0).collect();
+    for line in film.lines.iter_mut() {
+        for pixel in line.get_mut().unwrap().iter_mut() {
+            let mut values = [0.0; 17];
+            for value in values.iter_mut() {
+                *value = reader.f64().ok_or_else(truncated)?;
+            }
+            let mut counts = [0_u32; 4];
+            for value in counts.iter_mut() {
+                *value = reader.u32().ok_or_else(truncated)?;
+            }
+            *pixel = PixelEstimate {
+                sum: Color::new(values[0], values[1], values[2]),
+                count: counts[0],
+                mean: values[3],
+                m2: values[4],
+                features: Features {
+                    albedo: Color::new(values[5], values[6], values[7]),
+                    normal: Vec3::new(values[8], values[9], values[10]),
+                    depth: values[11],
+                    position: Vec3::new(values[12], values[13], values[14]),
+                    u: values[15],
+                    v: values[16],
+                    hits: counts[1],
+                    object_id: counts[2],
+                    material_id: counts[3],
+                },
+            };
+        }
+    }
+    for tile in film.splats.iter_mut() {
+        for splat in tile.get_mut().unwrap().splats.iter_mut() {
+            let mut values = [0.0; 4];
+            for value in values.iter_mut() {
+                *value = reader.f64().ok_or_else(truncated)?;
+            }
+            splat.sum = Color::new(values[0], values[1], values[2]);
+            splat.weight = values[3];
+        }
+    }
+    Ok(film)
+}
+
+// continues a render, exits if the file is not a checkpoint of it
+pub fn load(path: &str) -> AdaptiveFilm {
+    let film = read_film(path).unwrap_or_else(|msg| {
+        eprintln!("Cannot resume from {}: {}", path, msg);
+        std::process::exit(2);
+    });
+    show_resumed(film.pass, path);
+    film
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Some(head)
+    }
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::config;
+
+    fn temp_path(name: &str) -> String {
+        let path = env::temp_dir().join(format!("raytracer-{}-{}", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    // a film with every pixel and splat of its own
+    fn sample_film() -> AdaptiveFilm {
+        let mut film = AdaptiveFilm::new();
+        film.pass = 2;
+        film.batch = 3;
+        film.done.get_mut().unwrap()[0] = true;
+        for (y, line) in film.lines.iter_mut().enumerate() {
+            for (x, pixel) in line.get_mut().unwrap().iter_mut().enumerate() {
+                let value = (y * 100 + x) as f64;
+                pixel.add(Color::new(value, 0.5, -value));
+                pixel.features.depth = value + 0.25;
+                pixel.features.object_id = x as u32;
+            }
+        }
+        for (i, tile) in film.splats.iter_mut().enumerate() {
+            for (j, splat) in tile.get_mut().unwrap().splats.iter_mut().enumerate() {
+                splat.sum = Color::new(i as f64, j as f64, 0.1);
+                splat.weight = j as f64 + 0.5;
+            }
+        }
+        film
+    }
+
+    #[test]
+    fn round_trip() {
+        config::load_for_tests();
+        let film = sample_film();
+        let path = temp_path("round_trip");
+        write_film(&path, &film).unwrap();
+        let read = read_film(&path);
+        fs::remove_file(&path).ok();
+        let mut read = read.unwrap();
+
+        assert_eq!((read.pass, read.batch), (film.pass, film.batch));
+        assert_eq!(*read.done.get_mut().unwrap(), *film.done.lock().unwrap());
+        for (line, read_line) in film.lines.iter().zip(read.lines.iter_mut()) {
+            let line = line.lock().unwrap();
+            for (pixel, read_pixel) in line.iter().zip(read_line.get_mut().unwrap().iter()) {
+                assert_eq!(read_pixel.sum.0.to_bits(), pixel.sum.0.to_bits());
+                assert_eq!(read_pixel.sum.2.to_bits(), pixel.sum.2.to_bits());
+                assert_eq!(read_pixel.count, pixel.count);
+                assert_eq!(read_pixel.mean.to_bits(), pixel.mean.to_bits());
+                assert_eq!(read_pixel.m2.to_bits(), pixel.m2.to_bits());
+                assert_eq!(read_pixel.features.depth, pixel.features.depth);
+                assert_eq!(read_pixel.features.object_id, pixel.features.object_id);
+            }
+        }
+        for (tile, read_tile) in film.splats.iter().zip(read.splats.iter_mut()) {
+            let tile = tile.lock().unwrap();
+            for (splat, read_splat) in tile
+                .splats
+                .iter()
+                .zip(read_tile.get_mut().unwrap().splats.iter())
+            {
+                assert_eq!(read_splat.sum.0, splat.sum.0);
+                assert_eq!(read_splat.sum.1, splat.sum.1);
+                assert_eq!(read_splat.weight, splat.weight);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_renders() {
+        config::load_for_tests();
+        let path = temp_path("rejects_other_renders");
+        write_film(&path, &sample_film()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        // the same file rendered with the halton sampler
+        let sobol = [&5_u32.to_le_bytes()[..], b"sobol"].concat();
+        let halton = [&6_u32.to_le_bytes()[..], b"halton"].concat();
+        let at = bytes
+            .windows(sobol.len())
+            .position(|window| window == sobol)
+            .unwrap();
+        let patched = [&bytes[..at], &halton[..], &bytes[at + sobol.len()..]].concat();
+        fs::write(&path, &patched).unwrap();
+        let sampler = read_film(&path).err();
+
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let truncated = read_film(&path).err();
+        fs::write(&path, b"RTCKPT06").unwrap();
+        let old = read_film(&path).err();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(
+            sampler.as_deref(),
+            Some("it was rendered with the sampler halton, not sobol")
+        );
+        assert_eq!(truncated.as_deref(), Some("file is truncated"));
+        assert_eq!(old.as_deref(), Some("not a checkpoint file"));
+    }
+}
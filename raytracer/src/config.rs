@@ -0,0 +1,496 @@
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::{
+    aov::Aov, filter::Filter, integrator::Integrator, output::ImageFormat, pdf::mis::MisHeuristic,
+    sampler::SamplerType, status_bar::ProgressMode, tile::TileOrder, tonemap::ToneMap,
+};
+
+// Render settings, from the command line over an optional TOML file over the defaults.
+// The file uses the long option names as keys, e.g.
+//
+//     scene = "cornell_box"
+//     width = 800
+//     spp = 1000
+//
+// Set once in `main` before rendering, read everywhere through `config()`.
+
+#[derive(Parser, Deserialize, Default)]
+#[command(about = "A ray tracer following the Ray Tracing in One Weekend series")]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    #[arg(short, long, help = "TOML file with any of the options below")]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "random_scene, book2_final_scene, cornell_box, obj_test_scene, final_scene or a .json/.toml scene file"
+    )]
+    scene: Option<String>,
+    #[arg(long, help = "Image width in pixels")]
+    width: Option<u32>,
+    #[arg(long, help = "Image height in pixels, 16:10 to the width if not given")]
+    height: Option<u32>,
+    #[arg(long, help = "Samples per pixel")]
+    spp: Option<u32>,
+    #[arg(long, help = "Maximum path depth")]
+    depth: Option<i32>,
+    #[arg(long, help = "Render threads")]
+    threads: Option<u32>,
+    #[arg(
+        long,
+        help = "Order the tiles are rendered in, spiral if not given (path and BDPT only)"
+    )]
+    tile_order: Option<TileOrder>,
+    #[arg(long, help = "Light transport algorithm, path if not given")]
+    integrator: Option<Integrator>,
+    #[arg(
+        long,
+        help = "Sample pattern of the pixels, sobol if not given (not for pssmlt)"
+    )]
+    sampler: Option<SamplerType>,
+    #[arg(
+        long,
+        help = "Weighting of the light and bsdf samples, power if not given (path only)"
+    )]
+    mis: Option<MisHeuristic>,
+    #[arg(long, help = "Random seed, the same seed renders the same image")]
+    seed: Option<u64>,
+    #[arg(
+        long,
+        help = "Spend more samples on the noisy pixels, --spp is then the average over the image (path and BDPT only)"
+    )]
+    adaptive: bool,
+    #[arg(
+        long,
+        help = "With --adaptive, also write the samples per pixel as <output>_samples.jpg"
+    )]
+    heatmap: bool,
+    #[arg(
+        long,
+        help = "Count the rays, intersection tests and paths, print them and write them to <output>_stats.json, slows the render by about a tenth"
+    )]
+    stats: bool,
+    #[arg(
+        short,
+        long,
+        help = "Output image, .jpg, .png, .ppm or linear .exr, .hdr, .pfm; output/works/<scene>.jpg if not given"
+    )]
+    output: Option<String>,
+    #[arg(
+        long,
+        help = "Progress bars, plain lines or JSON events, bars only if stdout is a terminal by default"
+    )]
+    progress: Option<ProgressMode>,
+    #[arg(long, help = "JPEG quality, 1 to 100")]
+    quality: Option<u8>,
+    #[arg(long, help = "Write 16 bit instead of 8 bit PNG")]
+    png16: bool,
+    #[arg(
+        long,
+        value_name = "EV",
+        allow_negative_numbers = true,
+        help = "Exposure in stops for the .jpg, .png and .ppm images"
+    )]
+    exposure: Option<f64>,
+    #[arg(long, help = "Tone curve for the .jpg, .png and .ppm images")]
+    tonemap: Option<ToneMap>,
+    #[arg(long, help = "Luminance mapped to white by reinhard-extended")]
+    white: Option<f64>,
+    #[arg(long, help = "Pixel reconstruction filter (path and BDPT only)")]
+    filter: Option<Filter>,
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        help = "Filter radius, 0.5 for box, 1 for tent, 1.5 for gaussian and 2 for the others if not given"
+    )]
+    filter_radius: Option<f64>,
+    #[arg(
+        long,
+        help = "Denoise guided by the albedo, normal and depth of the first hits (path and BDPT only)"
+    )]
+    denoise: bool,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Also write these first-hit layers, as channels of an .exr output or as images next to the others (path and BDPT only)"
+    )]
+    aov: Vec<Aov>,
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help = "Add passes of samples to every pixel until this time is up instead of rendering --spp, e.g. 90s, 10m or 2h (path and BDPT only)"
+    )]
+    time_limit: Option<String>,
+    #[arg(
+        long,
+        value_name = "X,Y,W,H",
+        value_parser = parse_crop,
+        help = "Render only this pixel rectangle, from the top left corner, into an image of its size (path and BDPT only)"
+    )]
+    crop: Option<(u32, u32, u32, u32)>,
+    #[arg(
+        long,
+        help = "Keep the full image size for --crop, black outside the rectangle"
+    )]
+    crop_canvas: bool,
+    #[arg(
+        long,
+        value_name = "SPP",
+        help = "Write the image after every pass of this many samples per pixel (path and BDPT only)"
+    )]
+    progressive: Option<u32>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Save the render state to this file periodically and on Ctrl-C (path and BDPT only)"
+    )]
+    checkpoint: Option<String>,
+    #[arg(long, help = "Continue the render saved in the --checkpoint file")]
+    resume: bool,
+    #[arg(
+        long,
+        help = "Render with this many local worker processes and merge their parts (path and BDPT only)"
+    )]
+    workers: Option<u32>,
+    #[arg(
+        long,
+        value_name = "I/N",
+        value_parser = parse_worker,
+        help = "Render only the share I of N workers into <output>.partI"
+    )]
+    worker: Option<(u32, u32)>,
+    #[arg(
+        long,
+        value_name = "PART",
+        num_args = 1..,
+        help = "Write the image of the given worker parts instead of rendering"
+    )]
+    merge: Vec<String>,
+}
+
+// seconds, or with the unit s, m or h
+fn parse_duration(arg: &str) -> Option<Duration> {
+    let split = arg
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(split);
+    let scale = match unit {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    let seconds = number.trim().parse::<f64>().ok()? * scale;
+    (seconds > 0.0 && seconds.is_finite()).then(|| Duration::from_secs_f64(seconds))
+}
+
+fn parse_crop(arg: &str) -> Result<(u32, u32, u32, u32), String> {
+    let values: Vec<u32> = arg
+        .split(',')
+        .map(|value| value.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("expected X,Y,W,H, got {}", arg))?;
+    match values[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Ok((x, y, width, height)),
+        _ => Err(format!("expected X,Y,W,H with W, H > 0, got {}", arg)),
+    }
+}
+
+// for the crops of the config file too, which do not go through `parse_crop`; the end
+// of a crop may not fit in a u32
+fn crop_fits(
+    (x, y, width, height): (u32, u32, u32, u32),
+    image_width: u32,
+    image_height: u32,
+) -> bool {
+    let fits = |start: u32, size: u32, image: u32| {
+        size > 0 && start.checked_add(size).is_some_and(|end| end <= image)
+    };
+    fits(x, width, image_width) && fits(y, height, image_height)
+}
+
+fn parse_worker(arg: &str) -> Result<(u32, u32), String> {
+    let parsed = arg
+        .split_once('/')
+        .and_then(|(index, num)| Some((index.parse().ok()?, num.parse().ok()?)));
+    match parsed {
+        Some((index, num)) if index < num => Ok((index, num)),
+        _ => Err(format!("expected I/N with I < N, got {}", arg)),
+    }
+}
+
+pub struct Config {
+    pub scene: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: i32,
+    pub thread_num: u32,
+    pub tile_order: TileOrder,
+    pub integrator: Integrator,
+    pub sampler: SamplerType,
+    pub mis: MisHeuristic,
+    pub seed: u64,
+    pub adaptive: bool,
+    pub sample_heatmap: bool,
+    pub stats: bool,
+    pub output: String,
+    // never Auto
+    pub progress: ProgressMode,
+    pub jpeg_quality: u8,
+    pub png_16bit: bool,
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    pub white_point: f64,
+    pub filter: Filter,
+    pub filter_radius: f64,
+    pub denoise: bool,
+    pub aovs: Vec<Aov>,
+    pub time_limit: Option<Duration>,
+    // x, y, width and height, with y counted from the top row down
+    pub crop: Option<(u32, u32, u32, u32)>,
+    pub crop_canvas: bool,
+    pub progressive_spp: Option<u32>,
+    pub checkpoint: Option<String>,
+    pub resume: bool,
+    pub workers: Option<u32>,
+    pub worker: Option<(u32, u32)>,
+    pub merge: Vec<String>,
+}
+
+impl Config {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn config() -> &'static Config {
+    CONFIG.get().expect("Config is not loaded")
+}
+
+// parses the command line and the config file it names, exits on errors
+pub fn load() -> &'static Config {
+    let loaded = from_options(Options::parse());
+    CONFIG.get_or_init(|| loaded)
+}
+
+// a small Cornell box shared by all tests, which run in one process
+#[cfg(test)]
+pub fn load_for_tests() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        from_options(Options::parse_from([
+            "raytracer",
+            "--scene",
+            "cornell_box",
+            "--width",
+            "40",
+            "--height",
+            "30",
+            "--spp",
+            "4",
+            "--threads",
+            "2",
+        ]))
+    })
+}
+
+fn from_options(cli: Options) -> Config {
+    let file = match &cli.config {
+        Some(path) => {
+            let text = fs::read_to_string(path).unwrap_or_else(|err| {
+                eprintln!("Cannot read config file {}: {}", path.display(), err);
+                std::process::exit(2);
+            });
+            toml::from_str(&text).unwrap_or_else(|err| {
+                eprintln!("Invalid config file {}: {}", path.display(), err);
+                std::process::exit(2);
+            })
+        }
+        None => Options::default(),
+    };
+
+    let scene = cli
+        .scene
+        .or(file.scene)
+        .unwrap_or_else(|| "final_scene".to_string());
+    let image_width = cli.width.or(file.width).unwrap_or(2560).max(1);
+    let image_height = cli
+        .height
+        .or(file.height)
+        .unwrap_or((image_width as f64 / (16.0 / 10.0)) as u32)
+        .max(1);
+    let output = cli.output.or(file.output).unwrap_or_else(|| {
+        let stem = Path::new(&scene).file_stem().unwrap_or_default();
+        format!("output/works/{}.jpg", stem.to_string_lossy())
+    });
+
+    if ImageFormat::from_path(&output).is_none() {
+        eprintln!(
+            "Unknown image format of {}, use .jpg, .png, .ppm, .exr, .hdr or .pfm",
+            output
+        );
+        std::process::exit(2);
+    }
+
+    let filter = cli.filter.or(file.filter).unwrap_or(Filter::Box);
+    let worker = cli.worker.or(file.worker);
+    // every worker keeps its own checkpoint
+    let checkpoint = cli.checkpoint.or(file.checkpoint).map(|path| match worker {
+        Some((index, _)) => format!("{}.{}", path, index),
+        None => path,
+    });
+    let resume = cli.resume || file.resume;
+    if resume && checkpoint.is_none() {
+        eprintln!("--resume needs the --checkpoint file to continue from");
+        std::process::exit(2);
+    }
+
+    let time_limit = cli.time_limit.or(file.time_limit).map(|arg| {
+        parse_duration(&arg).unwrap_or_else(|| {
+            eprintln!("Invalid time limit {}, use e.g. 600, 90s, 10m or 2h", arg);
+            std::process::exit(2);
+        })
+    });
+
+    let crop = cli.crop.or(file.crop);
+    if let Some((x, y, width, height)) = crop {
+        if !crop_fits((x, y, width, height), image_width, image_height) {
+            eprintln!(
+                "The crop {},{},{},{} is empty or not within the {}x{} image",
+                x, y, width, height, image_width, image_height
+            );
+            std::process::exit(2);
+        }
+    }
+
+    Config {
+        scene,
+        image_width,
+        image_height,
+        samples_per_pixel: cli.spp.or(file.spp).unwrap_or(8000).max(1),
+        max_depth: cli.depth.or(file.depth).unwrap_or(60),
+        thread_num: cli.threads.or(file.threads).unwrap_or(20).max(1),
+        tile_order: cli
+            .tile_order
+            .or(file.tile_order)
+            .unwrap_or(TileOrder::Spiral),
+        integrator: cli
+            .integrator
+            .or(file.integrator)
+            .unwrap_or(Integrator::Path),
+        sampler: cli.sampler.or(file.sampler).unwrap_or(SamplerType::Sobol),
+        mis: cli.mis.or(file.mis).unwrap_or(MisHeuristic::Power),
+        seed: cli.seed.or(file.seed).unwrap_or(0),
+        adaptive: cli.adaptive || file.adaptive,
+        sample_heatmap: cli.heatmap || file.heatmap,
+        stats: cli.stats || file.stats,
+        output,
+        progress: match cli.progress.or(file.progress).unwrap_or(ProgressMode::Auto) {
+            ProgressMode::Auto if io::stdout().is_terminal() => ProgressMode::Bar,
+            ProgressMode::Auto => ProgressMode::Plain,
+            mode => mode,
+        },
+        jpeg_quality: cli.quality.or(file.quality).unwrap_or(60).clamp(1, 100),
+        png_16bit: cli.png16 || file.png16,
+        exposure: cli.exposure.or(file.exposure).unwrap_or(0.0),
+        tone_map: cli.tonemap.or(file.tonemap).unwrap_or(ToneMap::None),
+        white_point: cli.white.or(file.white).unwrap_or(4.0).max(1e-3),
+        filter,
+        filter_radius: cli
+            .filter_radius
+            .or(file.filter_radius)
+            .unwrap_or(filter.default_radius())
+            .max(0.5),
+        denoise: cli.denoise || file.denoise,
+        aovs: if cli.aov.is_empty() {
+            file.aov
+        } else {
+            cli.aov
+        },
+        time_limit,
+        crop,
+        crop_canvas: cli.crop_canvas || file.crop_canvas,
+        progressive_spp: cli.progressive.or(file.progressive).map(|spp| spp.max(1)),
+        checkpoint,
+        resume,
+        // workers started from a config file with `workers` must not start more
+        workers: cli
+            .workers
+            .or(file.workers)
+            .filter(|_| worker.is_none())
+            .map(|num| num.max(1)),
+        worker,
+        merge: if cli.merge.is_empty() {
+            file.merge
+        } else {
+            cli.merge
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("600"), Some(Duration::from_secs(600)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        for invalid in ["", "s", "10d", "-5s", "0", "inf", "10 minutes"] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn crops() {
+        assert_eq!(parse_crop("10,20,30,40"), Ok((10, 20, 30, 40)));
+        assert_eq!(parse_crop(" 0, 0, 1, 1"), Ok((0, 0, 1, 1)));
+        for invalid in [
+            "",
+            "1,2,3",
+            "1,2,3,4,5",
+            "1,2,0,4",
+            "1,2,3,0",
+            "-1,2,3,4",
+            "a,b,c,d",
+        ] {
+            assert!(parse_crop(invalid).is_err(), "{}", invalid);
+        }
+        // empty crops from a config file
+        assert!(!crop_fits((10, 10, 0, 5), 64, 48));
+        assert!(!crop_fits((10, 10, 5, 0), 64, 48));
+    }
+
+    #[test]
+    fn crop_within_the_image() {
+        assert!(crop_fits((0, 0, 64, 48), 64, 48));
+        assert!(crop_fits((10, 20, 54, 28), 64, 48));
+        assert!(!crop_fits((10, 20, 55, 28), 64, 48));
+        assert!(!crop_fits((10, 21, 54, 28), 64, 48));
+        // x + width wraps around to within the image
+        assert!(!crop_fits((u32::MAX, 0, 2, 1), 64, 48));
+        assert!(!crop_fits((0, u32::MAX - 1, 1, 3), 64, 48));
+    }
+
+    #[test]
+    fn workers() {
+        assert_eq!(parse_worker("0/1"), Ok((0, 1)));
+        assert_eq!(parse_worker("3/4"), Ok((3, 4)));
+        for invalid in ["", "4/4", "5/4", "1", "1/", "/2", "a/b", "-1/2"] {
+            assert!(parse_worker(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
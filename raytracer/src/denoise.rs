@@ -0,0 +1,228 @@
+use rayon::prelude::*;
+
+use crate::{
+    adaptive::AdaptiveFilm,
+    basic::vec3::{dot, Color, Vec3},
+    config::config,
+    DENOISE_ITERATIONS, DENOISE_SIGMA_ALBEDO, DENOISE_SIGMA_DEPTH, DENOISE_SIGMA_LUMINANCE,
+    DENOISE_SIGMA_NORMAL,
+};
+
+// Edge-avoiding À-trous wavelet filter (Dammertz et al. 2010) with the luminance
+// weighted by the variance of the pixel means as in SVGF (Schied et al. 2017).
+// It smooths the radiance divided by the albedo, so textures stay sharp, with
+// 5x5 kernels spread wider every iteration.
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+struct Guide {
+    // pixels outside the --crop or of other workers have no samples and stay out
+    sampled: bool,
+    albedo: Color,
+    // unit length, zero for the sky
+    normal: Vec3,
+    depth: f64,
+}
+
+// `lines` in the layout of `write_color`, the guides come from `film`
+pub fn denoise(lines: &[(u32, Vec<Color>)], film: &AdaptiveFilm) -> Vec<(u32, Vec<Color>)> {
+    let (width, height) = (
+        config().image_width as usize,
+        config().image_height as usize,
+    );
+    let spp = config().samples_per_pixel as f64;
+
+    let mut guides = Vec::with_capacity(width * height);
+    let mut variance = Vec::with_capacity(width * height);
+    for line in film.lines.iter() {
+        for pixel in line.lock().unwrap().iter() {
+            let count = pixel.count.max(1) as f64;
+            let normal = pixel.features.normal / count;
+            guides.push(Guide {
+                sampled: pixel.count > 0,
+                albedo: pixel.features.albedo / count,
+                normal: if normal.length() > 1e-6 {
+                    normal.unit_vec()
+                } else {
+                    Vec3::default()
+                },
+                depth: pixel.features.depth / count,
+            });
+            variance.push(pixel.mean_variance());
+        }
+    }
+
+    // radiance over albedo, the variance along with it
+    let mut color = vec![Color::default(); width * height];
+    for (py, line) in lines {
+        for (px, c) in line.iter().enumerate() {
+            let i = *py as usize * width + px;
+            let albedo = guides[i].albedo;
+            let divide = |c: f64, a: f64| if a > 1e-3 { c / spp / a } else { c / spp };
+            color[i] = Color::new(
+                divide(c.0, albedo.0),
+                divide(c.1, albedo.1),
+                divide(c.2, albedo.2),
+            );
+            let luminance = albedo.luminance();
+            if luminance > 1e-3 {
+                variance[i] /= luminance * luminance;
+            }
+        }
+    }
+
+    for iteration in 0..DENOISE_ITERATIONS {
+        let step = 1_i64 << iteration;
+        let mut next_color = vec![Color::default(); width * height];
+        let mut next_variance = vec![0.0; width * height];
+        next_color
+            .par_chunks_mut(width)
+            .zip(next_variance.par_chunks_mut(width))
+            .enumerate()
+            .for_each(|(py, (color_row, variance_row))| {
+                for px in 0..width {
+                    let p = py * width + px;
+                    let (c, v) = atrous(p, step, width, height, &color, &variance, &guides);
+                    color_row[px] = c;
+                    variance_row[px] = v;
+                }
+            });
+        color = next_color;
+        variance = next_variance;
+    }
+
+    lines
+        .iter()
+        .map(|(py, line)| {
+            let row = (0..line.len()).map(|px| {
+                let i = *py as usize * width + px;
+                let (c, albedo) = (color[i], guides[i].albedo);
+                let multiply = |c: f64, a: f64| if a > 1e-3 { c * a * spp } else { c * spp };
+                Color::new(
+                    multiply(c.0, albedo.0),
+                    multiply(c.1, albedo.1),
+                    multiply(c.2, albedo.2),
+                )
+            });
+            (*py, row.collect())
+        })
+        .collect()
+}
+
+fn atrous(
+    p: usize,
+    step: i64,
+    width: usize,
+    height: usize,
+    color: &[Color],
+    variance: &[f64],
+    guides: &[Guide],
+) -> (Color, f64) {
+    let guide = &guides[p];
+    if !guide.sampled {
+        return (color[p], 0.0);
+    }
+    let (px, py) = ((p % width) as i64, (p / width) as i64);
+    let luminance = color[p].luminance();
+    let luminance_scale = DENOISE_SIGMA_LUMINANCE * variance[p].sqrt() + 1e-6;
+
+    let mut sum = Color::default();
+    let mut sum_variance = 0.0;
+    let mut sum_weight = 0.0;
+    for (ky, hy) in KERNEL.iter().enumerate() {
+        let qy = py + (ky as i64 - 2) * step;
+        if qy < 0 || qy >= height as i64 {
+            continue;
+        }
+        for (kx, hx) in KERNEL.iter().enumerate() {
+            let qx = px + (kx as i64 - 2) * step;
+            if qx < 0 || qx >= width as i64 {
+                continue;
+            }
+            let q = qy as usize * width + qx as usize;
+            let other = &guides[q];
+            if !other.sampled {
+                continue;
+            }
+
+            let w_normal = match (guide.depth > 0.0, other.depth > 0.0) {
+                (true, true) => dot(&guide.normal, &other.normal)
+                    .max(0.0)
+                    .powf(DENOISE_SIGMA_NORMAL),
+                (false, false) => 1.0,
+                _ => 0.0,
+            };
+            let w_depth = (-(guide.depth - other.depth).abs()
+                / (DENOISE_SIGMA_DEPTH * step as f64 * guide.depth.max(other.depth) + 1e-6))
+                .exp();
+            let albedo_difference = guide.albedo - other.albedo;
+            let w_albedo = (-dot(&albedo_difference, &albedo_difference)
+                / (DENOISE_SIGMA_ALBEDO * DENOISE_SIGMA_ALBEDO))
+                .exp();
+            let w_luminance = (-(luminance - color[q].luminance()).abs() / luminance_scale).exp();
+
+            let weight = hx * hy * w_normal * w_depth * w_albedo * w_luminance;
+            if weight == 0.0 {
+                continue;
+            }
+            sum += color[q] * weight;
+            sum_variance += weight * weight * variance[q];
+            sum_weight += weight;
+        }
+    }
+    // the center always weighs in, so `sum_weight` is positive
+    (sum / sum_weight, sum_variance / (sum_weight * sum_weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    // a film of one sample per pixel, without any in the pixels `empty` picks
+    fn sparse_film(empty: impl Fn(usize, usize) -> bool) -> AdaptiveFilm {
+        let film = AdaptiveFilm::new();
+        for (y, line) in film.lines.iter().enumerate() {
+            for (x, pixel) in line.lock().unwrap().iter_mut().enumerate() {
+                if empty(x, y) {
+                    continue;
+                }
+                let value = ((x * 7 + y * 13) % 10) as f64 / 4.0;
+                pixel.add(Color::new(value, value * 0.5, 1.0));
+                pixel.features.albedo = Color::new(0.5, 0.5, 0.5);
+                pixel.features.normal = Vec3::new(0.0, 0.0, 1.0);
+                pixel.features.depth = 1.0 + x as f64 / 100.0;
+            }
+        }
+        film
+    }
+
+    fn assert_finite(film: &AdaptiveFilm) {
+        let lines: Vec<(u32, Vec<Color>)> = film
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(y, line)| {
+                let line = line.lock().unwrap();
+                (y as u32, line.iter().map(|pixel| pixel.value()).collect())
+            })
+            .collect();
+        for (_, line) in denoise(&lines, film) {
+            for c in line {
+                assert!(c.0.is_finite() && c.1.is_finite() && c.2.is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn one_sample_stays_finite() {
+        config::load_for_tests();
+        assert_finite(&sparse_film(|_, _| false));
+    }
+
+    #[test]
+    fn unsampled_pixels_stay_out() {
+        config::load_for_tests();
+        assert_finite(&sparse_film(|x, y| !(10..30).contains(&x) || y < 8));
+    }
+}
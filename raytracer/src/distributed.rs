@@ -0,0 +1,111 @@
+use std::{
+    env,
+    process::{exit, Command, Stdio},
+};
+
+use console::style;
+
+use crate::{
+    adaptive::AdaptiveFilm,
+    checkpoint::{read_film, write_film},
+    config::config,
+    stats,
+    status_bar::{show_output, show_part_merged, show_worker_done},
+};
+
+// Distributed rendering: `--worker I/N` renders every N-th tile of --tile-order
+// starting at I and writes its film to a part file next to the output instead of
+// the image. `--workers N` runs N local workers splitting the threads and merges
+// their parts, `--merge` merges parts copied over from other machines.
+// Without adaptive sampling the merged image is the one of a single process, with
+// it every worker spends the budget of its own pixels. The stats of a part are in
+// `<part>.json`.
+
+pub fn owns(tile_index: usize) -> bool {
+    match config().worker {
+        Some((index, num)) => tile_index % num as usize == index as usize,
+        None => true,
+    }
+}
+
+pub fn part_path(index: u32) -> String {
+    let path = config().output.as_str();
+    let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+    format!("{}.part{}", stem, index)
+}
+
+pub fn write_part(index: u32, film: &AdaptiveFilm) {
+    let path = part_path(index);
+    show_output("part", &path, write_film(&path, film));
+    if config().stats {
+        let stats_path = format!("{}.json", path);
+        show_output("stats", &stats_path, stats::write(&stats_path));
+    }
+}
+
+pub fn merge(paths: &[String]) -> AdaptiveFilm {
+    let mut film = AdaptiveFilm::new();
+    for path in paths {
+        let part = read_film(path).unwrap_or_else(|msg| {
+            eprintln!("Cannot merge {}: {}", path, msg);
+            exit(2);
+        });
+        film.merge(part);
+        stats::merge(&format!("{}.json", path));
+        show_part_merged(path);
+    }
+    film
+}
+
+// runs this binary again once per worker with the same options
+pub fn run_workers(num: u32) -> AdaptiveFilm {
+    // the worker options replace these
+    let mut args = Vec::new();
+    let mut skip_value = false;
+    for arg in env::args().skip(1) {
+        if skip_value {
+            skip_value = false;
+        } else if arg == "--workers" || arg == "--threads" {
+            skip_value = true;
+        } else if !arg.starts_with("--workers=") && !arg.starts_with("--threads=") {
+            args.push(arg);
+        }
+    }
+
+    let exe = env::current_exe().expect("Cannot find the executable for the workers");
+    let mut children = Vec::new();
+    for index in 0..num {
+        let threads =
+            (config().thread_num / num + (index < config().thread_num % num) as u32).max(1);
+        let child = Command::new(&exe)
+            .args(&args)
+            .arg(format!("--worker={}/{}", index, num))
+            .arg(format!("--threads={}", threads))
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("Starting worker fails");
+        children.push(child);
+    }
+
+    let mut failed = false;
+    for (index, mut child) in children.into_iter().enumerate() {
+        let status = child.wait().expect("Waiting for worker fails");
+        if status.success() {
+            show_worker_done(index as u32);
+        } else {
+            eprintln!("{} {} ({})", style("Worker failed").red(), index, status);
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+
+    let paths: Vec<String> = (0..num).map(part_path).collect();
+    let film = merge(&paths);
+    for path in paths {
+        let _ = std::fs::remove_file(format!("{}.json", path));
+        let _ = std::fs::remove_file(path);
+    }
+    film
+}
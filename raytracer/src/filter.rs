@@ -0,0 +1,222 @@
+use std::{f64::consts::PI, sync::OnceLock};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{basic::vec3::Color, config::config, tile::Tile};
+
+// Pixel reconstruction: every sample is weighted into the pixels whose centers are
+// within the filter radius, and a pixel is its weighted sum over its weight sum.
+// The weights are normalized to integrate to 1 over the plane.
+// Pixels at the image borders are normalized by the weights they actually got.
+// The box of radius 0.5 is the plain average of the samples inside the pixel.
+
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    // B = C = 1/3
+    Mitchell,
+    // windowed sinc with as many lobes as the radius
+    Lanczos,
+}
+
+impl Filter {
+    pub fn default_radius(self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell | Filter::Lanczos => 2.0,
+        }
+    }
+
+    // separable, `x` is the offset from the pixel center in pixels
+    fn eval_1d(self, x: f64, radius: f64) -> f64 {
+        // half-open, so a sample on the border of two boxes counts once
+        if x < -radius || x >= radius {
+            return 0.0;
+        }
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => radius - x.abs(),
+            Filter::Gaussian => {
+                const ALPHA: f64 = 2.0;
+                ((-ALPHA * x * x).exp() - (-ALPHA * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let x = (2.0 * x / radius).abs();
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            Filter::Lanczos => sinc(x) * sinc(x / radius),
+        }
+    }
+
+    // of `eval_1d` over the radius, by the midpoint rule
+    fn integral_1d(self, radius: f64) -> f64 {
+        const STEPS: u32 = 1024;
+        let dx = 2.0 * radius / STEPS as f64;
+        (0..STEPS)
+            .map(|i| self.eval_1d(-radius + (i as f64 + 0.5) * dx, radius))
+            .sum::<f64>()
+            * dx
+    }
+}
+
+// the weight of the configured filter for a sample at `dx`, `dy` from the pixel center
+pub fn weight(dx: f64, dy: f64) -> f64 {
+    static SCALE: OnceLock<f64> = OnceLock::new();
+    let (filter, radius) = (config().filter, config().filter_radius);
+    let scale = *SCALE.get_or_init(|| 1.0 / filter.integral_1d(radius).powi(2));
+    filter.eval_1d(dx, radius) * filter.eval_1d(dy, radius) * scale
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+// pixels beyond a tile that its samples reach
+pub fn margin() -> u32 {
+    (config().filter_radius + 0.5).ceil() as u32
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Splat {
+    pub sum: Color,
+    pub weight: f64,
+}
+
+// The weighted samples of one tile into the tile and its margin. Every tile only
+// adds to its own, and they are summed in tile order for the image, so the result
+// does not depend on the threads.
+#[derive(Clone)]
+pub struct SplatTile {
+    x0: i64,
+    y0: i64,
+    width: usize,
+    pub splats: Vec<Splat>,
+}
+
+impl SplatTile {
+    pub fn new(tile: &Tile) -> Self {
+        let margin = margin() as i64;
+        let width = (tile.x1 - tile.x0) as usize + 2 * margin as usize;
+        let height = (tile.y1 - tile.y0) as usize + 2 * margin as usize;
+        SplatTile {
+            x0: tile.x0 as i64 - margin,
+            y0: tile.y0 as i64 - margin,
+            width,
+            splats: vec![Splat::default(); width * height],
+        }
+    }
+
+    // the sample at (x, y) in pixel units of the image
+    pub fn add(&mut self, x: f64, y: f64, color: Color) {
+        let margin = margin() as i64;
+        let (px, py) = (x.floor() as i64, y.floor() as i64);
+        for qy in (py - margin).max(0)..(py + margin + 1).min(config().image_height as i64) {
+            for qx in (px - margin).max(0)..(px + margin + 1).min(config().image_width as i64) {
+                let weight = weight(x - (qx as f64 + 0.5), y - (qy as f64 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+                let splat = &mut self.splats
+                    [(qy - self.y0) as usize * self.width + (qx - self.x0) as usize];
+                splat.sum += color * weight;
+                splat.weight += weight;
+            }
+        }
+    }
+
+    // adds the splats into the image sized `splats`
+    pub fn add_to(&self, splats: &mut [Splat]) {
+        let image_width = config().image_width as i64;
+        for (row, line) in self.splats.chunks(self.width).enumerate() {
+            let y = self.y0 + row as i64;
+            if y < 0 || y >= config().image_height as i64 {
+                continue;
+            }
+            for (column, splat) in line.iter().enumerate() {
+                let x = self.x0 + column as i64;
+                if x < 0 || x >= image_width {
+                    continue;
+                }
+                let target = &mut splats[(y * image_width + x) as usize];
+                target.sum += splat.sum;
+                target.weight += splat.weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // of the normalized 2D filter over the square around its support
+    fn integral_2d(filter: Filter, radius: f64) -> f64 {
+        let norm = filter.integral_1d(radius).powi(2);
+        let steps = 200;
+        let d = 2.0 * radius / steps as f64;
+        let at = |i: u32| -radius + (i as f64 + 0.5) * d;
+        (0..steps)
+            .flat_map(|i| (0..steps).map(move |j| (at(i), at(j))))
+            .map(|(x, y)| filter.eval_1d(x, radius) * filter.eval_1d(y, radius) / norm)
+            .sum::<f64>()
+            * d
+            * d
+    }
+
+    #[test]
+    fn weights_integrate_to_one() {
+        for &filter in Filter::value_variants() {
+            for radius in [filter.default_radius(), 0.5, 1.25, 3.0] {
+                let integral = integral_2d(filter, radius);
+                assert!(
+                    (integral - 1.0).abs() < 1e-3,
+                    "{} at radius {}",
+                    integral,
+                    radius
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn box_is_unscaled() {
+        assert_eq!(Filter::Box.integral_1d(0.5), 1.0);
+    }
+
+    #[test]
+    fn pixel_weights_sum_to_one() {
+        // box, tent and Mitchell at their radius add up to 1 over the pixel lattice
+        for &filter in [Filter::Box, Filter::Tent, Filter::Mitchell].iter() {
+            let radius = filter.default_radius();
+            let norm = filter.integral_1d(radius);
+            for i in 0..10 {
+                let x = i as f64 / 10.0 + 0.05;
+                let sum: f64 = (-3..=3)
+                    .map(|k| filter.eval_1d(x - (k as f64 + 0.5), radius) / norm)
+                    .sum();
+                assert!((sum - 1.0).abs() < 1e-3, "{} at {}", sum, x);
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    basic::ray::Ray,
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
     bvh::aabb::{surrounding_box, Aabb},
 };
 
@@ -48,10 +51,18 @@ impl Hittable for HittableList {
             *output_box = if first_box {
                 tmp_box
             } else {
-                surrounding_box(&output_box, &tmp_box)
+                surrounding_box(output_box, &tmp_box)
             };
             first_box = false;
         }
         true
     }
+    // of the first object emitting there
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.objects
+            .iter()
+            .map(|object| object.emitted_at(p, w))
+            .find(|emitted| emitted.max_component() > 0.0)
+            .unwrap_or_default()
+    }
 }
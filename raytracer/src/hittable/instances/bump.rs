@@ -5,7 +5,7 @@ use crate::{
         clamp,
         onb::Onb,
         ray::Ray,
-        vec3::{Point3, Vec3},
+        vec3::{Color, Point3, Vec3},
     },
     bvh::aabb::Aabb,
     hittable::{HitRecord, Hittable},
@@ -92,4 +92,7 @@ impl<TH: Hittable> Hittable for BumpSurface<TH> {
     fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
         self.obj.bounding_box(time0, time1, output_box)
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.obj.emitted_at(p, w)
+    }
 }
@@ -1,4 +1,4 @@
-use std::f64::{consts::E, INFINITY};
+use std::f64::consts::E;
 
 use crate::{
     basic::{
@@ -9,6 +9,7 @@ use crate::{
     bvh::aabb::Aabb,
     hittable::{HitRecord, Hittable},
     material::isotropic::Isotropic,
+    sampler::path_1d,
     texture::{solid_color_texture::SolidColor, Texture},
 };
 
@@ -50,7 +51,10 @@ where
         let mut rec1 = None;
         let mut rec2 = None;
 
-        if !self.boundary.hit(r, -INFINITY, INFINITY, &mut rec1) {
+        if !self
+            .boundary
+            .hit(r, -f64::INFINITY, f64::INFINITY, &mut rec1)
+        {
             return false;
         }
 
@@ -62,7 +66,7 @@ where
 
         if !self
             .boundary
-            .hit(r, rec1_data.t + 0.0001, INFINITY, &mut rec2)
+            .hit(r, rec1_data.t + 0.0001, f64::INFINITY, &mut rec2)
         {
             return false;
         }
@@ -88,7 +92,7 @@ where
 
         let ray_length = r.dir.length();
         let distance_inside_boundary = (rec2_data.t - rec1_data.t) * ray_length;
-        let hit_distance = self.neg_inv_density * random_double_unit().log(E);
+        let hit_distance = self.neg_inv_density * (1.0 - path_1d()).log(E);
 
         if hit_distance > distance_inside_boundary {
             return false;
@@ -104,6 +108,7 @@ where
             u: Default::default(),
             v: Default::default(),
             front_face: true,
+            object_id: 0,
         });
 
         if debugging {
@@ -1,4 +1,7 @@
-use crate::hittable::Hittable;
+use crate::{
+    basic::vec3::{Color, Point3, Vec3},
+    hittable::Hittable,
+};
 
 #[derive(Clone)]
 pub struct FlipFace<TH>
@@ -39,4 +42,8 @@ where
     ) -> bool {
         self.obj.bounding_box(time0, time1, output_box)
     }
+    // the front face of the flipped surface is seen from the other side
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.obj.emitted_at(p, &-*w)
+    }
 }
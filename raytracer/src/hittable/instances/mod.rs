@@ -2,4 +2,5 @@ pub mod bump;
 pub mod constant_medium;
 pub mod flip_face;
 pub mod rotate_y;
+pub mod tagged;
 pub mod translate;
@@ -1,7 +1,9 @@
-use std::f64::INFINITY;
-
 use crate::{
-    basic::{degrees_to_radians, ray::Ray, vec3::Vec3},
+    basic::{
+        degrees_to_radians,
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
     bvh::aabb::Aabb,
     hittable::{HitRecord, Hittable},
 };
@@ -68,6 +70,17 @@ where
 
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        // into the object space, like the rays of `hit`
+        let rotate = |v: &Vec3| {
+            Vec3(
+                self.cos_theta * v.0 - self.sin_theta * v.2,
+                v.1,
+                self.sin_theta * v.0 + self.cos_theta * v.2,
+            )
+        };
+        self.obj.emitted_at(&rotate(p), &rotate(w))
+    }
 }
 
 impl<TH> RotateY<TH>
@@ -81,8 +94,8 @@ where
         let cos_theta = radians.cos();
         let mut bbox = Default::default();
         let hasbox = obj.bounding_box(0.0, 1.0, &mut bbox);
-        let mut min = Vec3(INFINITY, INFINITY, INFINITY);
-        let mut max = Vec3(-INFINITY, -INFINITY, -INFINITY);
+        let mut min = Vec3(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vec3(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY);
 
         for i in 0..2 {
             for j in 0..2 {
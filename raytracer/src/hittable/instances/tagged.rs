@@ -0,0 +1,41 @@
+use crate::{
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
+    bvh::aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+};
+
+// gives the hits of `obj` an object ID for the AOVs, unless an inner tag did
+#[derive(Clone)]
+pub struct Tagged<TH>
+where
+    TH: Hittable,
+{
+    pub obj: TH,
+    pub id: u32,
+}
+
+impl<TH> Hittable for Tagged<TH>
+where
+    TH: Hittable,
+{
+    fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        if !self.obj.hit(r, t_min, t_max, rec) {
+            return false;
+        }
+        if let Some(rec_data) = rec {
+            if rec_data.object_id == 0 {
+                rec_data.object_id = self.id;
+            }
+        }
+        true
+    }
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        self.obj.bounding_box(time0, time1, output_box)
+    }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.obj.emitted_at(p, w)
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    basic::{ray::Ray, vec3::Vec3},
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
     bvh::aabb::Aabb,
     hittable::{HitRecord, Hittable},
 };
@@ -44,4 +47,7 @@ where
         };
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.obj.emitted_at(&(*p - self.offset), w)
+    }
 }
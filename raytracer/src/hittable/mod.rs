@@ -1,7 +1,7 @@
 use crate::{
     basic::{
         ray::Ray,
-        vec3::{dot, Point3, Vec3},
+        vec3::{dot, Color, Point3, Vec3},
     },
     bvh::aabb::Aabb,
     material::Material,
@@ -20,11 +20,13 @@ pub struct HitRecord<'a> {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    // set by the innermost `Tagged` instance, 0 for untagged objects
+    pub object_id: u32,
 }
 
-impl<'a> HitRecord<'a> {
+impl HitRecord<'_> {
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
-        self.front_face = dot(&r.direction(), &outward_normal) < 0.0;
+        self.front_face = dot(&r.direction(), outward_normal) < 0.0;
         self.normal = if self.front_face {
             *outward_normal
         } else {
@@ -36,4 +38,50 @@ impl<'a> HitRecord<'a> {
 pub trait Hittable: Send + Sync {
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool;
     fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool;
+    // radiance the surface at `p` emits towards `w`, for the integrators tracing from
+    // the lights; only the shapes that can be lights emit here
+    fn emitted_at(&self, _p: &Point3, _w: &Vec3) -> Color {
+        Color::default()
+    }
+}
+
+// the emission of `mat` at the surface point `p`, seen from `p + w`
+pub fn emitted_towards(
+    mat: &dyn Material,
+    p: &Point3,
+    w: &Vec3,
+    outward_normal: &Vec3,
+    u: f64,
+    v: f64,
+) -> Color {
+    let r_in = Ray {
+        orig: *p + *w,
+        dir: -*w,
+        tm: 0.0,
+    };
+    let mut rec = HitRecord {
+        p: *p,
+        normal: *outward_normal,
+        mat_ptr: mat,
+        t: 1.0,
+        u,
+        v,
+        front_face: true,
+        object_id: 0,
+    };
+    rec.set_face_normal(&r_in, outward_normal);
+    mat.emitted(&r_in, &rec, u, v, p)
+}
+
+// lets the generic instances wrap objects only known at runtime, e.g. from a scene file
+impl Hittable for Box<dyn Hittable> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        self.as_ref().hit(r, t_min, t_max, rec)
+    }
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        self.as_ref().bounding_box(time0, time1, output_box)
+    }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.as_ref().emitted_at(p, w)
+    }
 }
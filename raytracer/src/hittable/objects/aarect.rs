@@ -1,17 +1,23 @@
-use std::f64::INFINITY;
-
 use crate::{
     basic::{
-        random_double,
         ray::Ray,
-        vec3::{dot, Point3, Vec3},
+        vec3::{dot, Color, Point3, Vec3},
     },
     bvh::aabb::Aabb,
-    hittable::{HitRecord, Hittable},
+    hittable::{emitted_towards, HitRecord, Hittable},
     material::Material,
     pdf::lightable_list::Lightable,
+    sampler::Sampler,
+    stats::{self, Counter},
 };
 
+// same slack as the bounding boxes
+const SURFACE_EPSILON: f64 = 0.0001;
+
+fn within(x: f64, x0: f64, x1: f64) -> bool {
+    x >= x0 - SURFACE_EPSILON && x <= x1 + SURFACE_EPSILON
+}
+
 #[derive(Clone, Copy)]
 pub struct XYRect<TM>
 where
@@ -37,6 +43,7 @@ where
         true
     }
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::PrimitiveTests);
         let t = (self.k - r.orig.2) / r.dir.2;
         if t < t_min || t > t_max {
             return false;
@@ -55,12 +62,24 @@ where
             u: (x - self.x0) / (self.x1 - self.x0),
             v: (y - self.y0) / (self.y1 - self.y0),
             front_face: Default::default(),
+            object_id: 0,
         };
         rec_data.set_face_normal(r, &outward_normal);
 
         *rec = Some(rec_data);
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        if (p.2 - self.k).abs() >= SURFACE_EPSILON
+            || !within(p.0, self.x0, self.x1)
+            || !within(p.1, self.y0, self.y1)
+        {
+            return Color::default();
+        }
+        let u = (p.0 - self.x0) / (self.x1 - self.x0);
+        let v = (p.1 - self.y0) / (self.y1 - self.y0);
+        emitted_towards(&self.mat, p, w, &Vec3(0.0, 0.0, 1.0), u, v)
+    }
 }
 
 impl<TM: Material + Clone> Lightable for XYRect<TM> {
@@ -73,7 +92,7 @@ impl<TM: Material + Clone> Lightable for XYRect<TM> {
                 tm: 0.0,
             },
             0.001,
-            INFINITY,
+            f64::INFINITY,
             &mut rec,
         ) {
             return 0.0;
@@ -90,14 +109,36 @@ impl<TM: Material + Clone> Lightable for XYRect<TM> {
 
         distance_squared / (cosine * area)
     }
-    fn random(&self, origin: &Vec3) -> Vec3 {
+    fn random(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (u1, u2) = sampler.get_2d();
         let random_point = Vec3(
-            random_double(self.x0, self.x1),
-            random_double(self.y0, self.y1),
+            self.x0 + (self.x1 - self.x0) * u1,
+            self.y0 + (self.y1 - self.y0) * u2,
             self.k,
         );
         random_point - *origin
     }
+    fn on_surface(&self, p: &Point3) -> bool {
+        (p.2 - self.k).abs() < SURFACE_EPSILON
+            && p.0 >= self.x0 - SURFACE_EPSILON
+            && p.0 <= self.x1 + SURFACE_EPSILON
+            && p.1 >= self.y0 - SURFACE_EPSILON
+            && p.1 <= self.y1 + SURFACE_EPSILON
+    }
+    fn random_point(&self, sampler: &mut dyn Sampler) -> (Point3, Vec3) {
+        let (u1, u2) = sampler.get_2d();
+        (
+            Vec3(
+                self.x0 + (self.x1 - self.x0) * u1,
+                self.y0 + (self.y1 - self.y0) * u2,
+                self.k,
+            ),
+            Vec3(0.0, 0.0, 1.0),
+        )
+    }
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
 }
 
 #[derive(Clone)]
@@ -122,6 +163,7 @@ where
         true
     }
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::PrimitiveTests);
         let t = (self.k - r.orig.1) / r.dir.1;
         if t < t_min || t > t_max {
             return false;
@@ -140,6 +182,7 @@ where
             u: (x - self.x0) / (self.x1 - self.x0),
             v: (z - self.z0) / (self.z1 - self.z0),
             front_face: true,
+            object_id: 0,
         };
         rec_data.set_face_normal(r, &outward_normal);
 
@@ -147,6 +190,17 @@ where
 
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        if (p.1 - self.k).abs() >= SURFACE_EPSILON
+            || !within(p.0, self.x0, self.x1)
+            || !within(p.2, self.z0, self.z1)
+        {
+            return Color::default();
+        }
+        let u = (p.0 - self.x0) / (self.x1 - self.x0);
+        let v = (p.2 - self.z0) / (self.z1 - self.z0);
+        emitted_towards(&self.mat, p, w, &Vec3(0.0, 1.0, 0.0), u, v)
+    }
 }
 
 impl<TM: Material + Clone> Lightable for XZRect<TM> {
@@ -159,7 +213,7 @@ impl<TM: Material + Clone> Lightable for XZRect<TM> {
                 tm: 0.0,
             },
             0.001,
-            INFINITY,
+            f64::INFINITY,
             &mut rec,
         ) {
             return 0.0;
@@ -176,14 +230,36 @@ impl<TM: Material + Clone> Lightable for XZRect<TM> {
 
         distance_squared / (cosine * area)
     }
-    fn random(&self, origin: &Vec3) -> Vec3 {
+    fn random(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (u1, u2) = sampler.get_2d();
         let random_point = Vec3(
-            random_double(self.x0, self.x1),
+            self.x0 + (self.x1 - self.x0) * u1,
             self.k,
-            random_double(self.z0, self.z1),
+            self.z0 + (self.z1 - self.z0) * u2,
         );
         random_point - *origin
     }
+    fn on_surface(&self, p: &Point3) -> bool {
+        (p.1 - self.k).abs() < SURFACE_EPSILON
+            && p.0 >= self.x0 - SURFACE_EPSILON
+            && p.0 <= self.x1 + SURFACE_EPSILON
+            && p.2 >= self.z0 - SURFACE_EPSILON
+            && p.2 <= self.z1 + SURFACE_EPSILON
+    }
+    fn random_point(&self, sampler: &mut dyn Sampler) -> (Point3, Vec3) {
+        let (u1, u2) = sampler.get_2d();
+        (
+            Vec3(
+                self.x0 + (self.x1 - self.x0) * u1,
+                self.k,
+                self.z0 + (self.z1 - self.z0) * u2,
+            ),
+            Vec3(0.0, 1.0, 0.0),
+        )
+    }
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
 }
 
 #[derive(Clone)]
@@ -211,6 +287,7 @@ where
         true
     }
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::PrimitiveTests);
         let t = (self.k - r.orig.0) / r.dir.0;
         if t < t_min || t > t_max {
             return false;
@@ -229,6 +306,7 @@ where
             u: (y - self.y0) / (self.y1 - self.y0),
             v: (z - self.z0) / (self.z1 - self.z0),
             front_face: true,
+            object_id: 0,
         };
         rec_data.set_face_normal(r, &outward_normal);
 
@@ -236,6 +314,17 @@ where
 
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        if (p.0 - self.k).abs() >= SURFACE_EPSILON
+            || !within(p.1, self.y0, self.y1)
+            || !within(p.2, self.z0, self.z1)
+        {
+            return Color::default();
+        }
+        let u = (p.1 - self.y0) / (self.y1 - self.y0);
+        let v = (p.2 - self.z0) / (self.z1 - self.z0);
+        emitted_towards(&self.mat, p, w, &Vec3(1.0, 0.0, 0.0), u, v)
+    }
 }
 
 impl<TM: Material + Clone> Lightable for YZRect<TM> {
@@ -248,7 +337,7 @@ impl<TM: Material + Clone> Lightable for YZRect<TM> {
                 tm: 0.0,
             },
             0.001,
-            INFINITY,
+            f64::INFINITY,
             &mut rec,
         ) {
             return 0.0;
@@ -265,12 +354,34 @@ impl<TM: Material + Clone> Lightable for YZRect<TM> {
 
         distance_squared / (cosine * area)
     }
-    fn random(&self, origin: &Vec3) -> Vec3 {
+    fn random(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (u1, u2) = sampler.get_2d();
         let random_point = Vec3(
             self.k,
-            random_double(self.y0, self.y1),
-            random_double(self.z0, self.z1),
+            self.y0 + (self.y1 - self.y0) * u1,
+            self.z0 + (self.z1 - self.z0) * u2,
         );
         random_point - *origin
     }
+    fn on_surface(&self, p: &Point3) -> bool {
+        (p.0 - self.k).abs() < SURFACE_EPSILON
+            && p.1 >= self.y0 - SURFACE_EPSILON
+            && p.1 <= self.y1 + SURFACE_EPSILON
+            && p.2 >= self.z0 - SURFACE_EPSILON
+            && p.2 <= self.z1 + SURFACE_EPSILON
+    }
+    fn random_point(&self, sampler: &mut dyn Sampler) -> (Point3, Vec3) {
+        let (u1, u2) = sampler.get_2d();
+        (
+            Vec3(
+                self.k,
+                self.y0 + (self.y1 - self.y0) * u1,
+                self.z0 + (self.z1 - self.z0) * u2,
+            ),
+            Vec3(1.0, 0.0, 0.0),
+        )
+    }
+    fn area(&self) -> f64 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
 }
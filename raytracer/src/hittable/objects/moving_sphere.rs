@@ -6,6 +6,7 @@ use crate::{
     bvh::aabb::{surrounding_box, Aabb},
     hittable::{HitRecord, Hittable},
     material::Material,
+    stats::{self, Counter},
 };
 
 pub struct MovingSphere<TM>
@@ -35,6 +36,7 @@ where
     TM: Material,
 {
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::PrimitiveTests);
         let oc = r.orig - self.center(r.tm);
         let a = r.direction().length().powi(2);
         let half_b = dot(&oc, &r.direction());
@@ -60,6 +62,7 @@ where
             u: Default::default(),
             v: Default::default(),
             front_face: Default::default(),
+            object_id: 0,
         };
         let outward_normal = (r.at(root) - self.center(r.tm)) / self.radius;
         rec_data.set_face_normal(r, &outward_normal);
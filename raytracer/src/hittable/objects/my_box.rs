@@ -1,5 +1,8 @@
 use crate::{
-    basic::{ray::Ray, vec3::Point3},
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3, Vec3},
+    },
     bvh::aabb::Aabb,
     hittable::{hittable_list::HittableList, HitRecord, Hittable},
     material::Material,
@@ -16,7 +19,7 @@ pub struct MyBox {
 impl MyBox {
     pub fn new<TM>(p0: Point3, p1: Point3, mat: TM) -> MyBox
     where
-        TM: Material + Clone + Copy + 'static,
+        TM: Material + Clone + 'static,
     {
         let mut list = HittableList { objects: vec![] };
         list.add(Box::new(XYRect {
@@ -25,7 +28,7 @@ impl MyBox {
             y0: p0.1,
             y1: p1.1,
             k: p1.2,
-            mat,
+            mat: mat.clone(),
         }));
         list.add(Box::new(XYRect {
             x0: p0.0,
@@ -33,7 +36,7 @@ impl MyBox {
             y0: p0.1,
             y1: p1.1,
             k: p0.2,
-            mat,
+            mat: mat.clone(),
         }));
         list.add(Box::new(XZRect {
             x0: p0.0,
@@ -41,7 +44,7 @@ impl MyBox {
             z0: p0.2,
             z1: p1.2,
             k: p1.1,
-            mat,
+            mat: mat.clone(),
         }));
         list.add(Box::new(XZRect {
             x0: p0.0,
@@ -49,7 +52,7 @@ impl MyBox {
             z0: p0.2,
             z1: p1.2,
             k: p0.1,
-            mat,
+            mat: mat.clone(),
         }));
         list.add(Box::new(YZRect {
             y0: p0.1,
@@ -57,7 +60,7 @@ impl MyBox {
             z0: p0.2,
             z1: p1.2,
             k: p1.0,
-            mat,
+            mat: mat.clone(),
         }));
         list.add(Box::new(YZRect {
             y0: p0.1,
@@ -92,4 +95,7 @@ impl Hittable for MyBox {
         };
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        self.sides.emitted_at(p, w)
+    }
 }
@@ -1,15 +1,15 @@
-use std::f64::INFINITY;
-
 use crate::{
-    basic::{onb::Onb, random_double_unit, PI},
-    hittable::{HitRecord, Hittable},
+    basic::{onb::Onb, PI},
+    hittable::{emitted_towards, HitRecord, Hittable},
     pdf::lightable_list::Lightable,
+    sampler::Sampler,
+    stats::{self, Counter},
 };
 
 use crate::{
     basic::{
         ray::Ray,
-        vec3::{dot, Point3, Vec3},
+        vec3::{dot, random_unit_vector, Color, Point3, Vec3},
     },
     bvh::aabb::Aabb,
     material::Material,
@@ -44,6 +44,7 @@ impl<TM: Material> Sphere<TM> {
 
 impl<TM: Material> Hittable for Sphere<TM> {
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::PrimitiveTests);
         let oc = r.orig - self.center;
         let a = r.direction().length().powi(2);
         let half_b = dot(&oc, &r.direction());
@@ -70,6 +71,7 @@ impl<TM: Material> Hittable for Sphere<TM> {
             u: Default::default(),
             v: Default::default(),
             front_face: Default::default(),
+            object_id: 0,
         };
         rec_data.set_face_normal(r, &outward_normal);
         Sphere::<TM>::get_sphere_uv(
@@ -89,6 +91,15 @@ impl<TM: Material> Hittable for Sphere<TM> {
         };
         true
     }
+    fn emitted_at(&self, p: &Point3, w: &Vec3) -> Color {
+        let outward_normal = (*p - self.center) / self.radius;
+        if (outward_normal.length() - 1.0).abs() >= 1e-6 {
+            return Color::default();
+        }
+        let (mut u, mut v) = (0.0, 0.0);
+        Sphere::<TM>::get_sphere_uv(self.is_ground, &outward_normal, &mut u, &mut v);
+        emitted_towards(&self.mat, p, w, &outward_normal, u, v)
+    }
 }
 
 impl<TM: Material> Lightable for Sphere<TM> {
@@ -101,7 +112,7 @@ impl<TM: Material> Lightable for Sphere<TM> {
                 tm: 0.0,
             },
             0.001,
-            INFINITY,
+            f64::INFINITY,
             &mut rec,
         ) {
             return 0.0;
@@ -111,17 +122,43 @@ impl<TM: Material> Lightable for Sphere<TM> {
         let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
         1.0 / solid_angle
     }
-    fn random(&self, o: &Vec3) -> Vec3 {
+    fn random(&self, o: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
         let direction = self.center - *o;
         let distance_squared = direction.length().powi(2);
         let uvw = Onb::build_from_w(&direction);
-        uvw.local_by_vec3(random_to_sphere(self.radius, distance_squared))
+        let dir = uvw.local_by_vec3(random_to_sphere(self.radius, distance_squared, sampler));
+
+        // the sampled point is where the direction first meets the sphere
+        let mut rec = None;
+        if self.hit(
+            &Ray {
+                orig: *o,
+                dir,
+                tm: 0.0,
+            },
+            0.001,
+            f64::INFINITY,
+            &mut rec,
+        ) {
+            rec.expect("No hit record").p - *o
+        } else {
+            dir
+        }
+    }
+    fn on_surface(&self, p: &Point3) -> bool {
+        ((*p - self.center).length() - self.radius).abs() < 1e-6 * self.radius.max(1.0)
+    }
+    fn random_point(&self, sampler: &mut dyn Sampler) -> (Point3, Vec3) {
+        let normal = random_unit_vector(sampler);
+        (self.center + normal * self.radius, normal)
+    }
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius.powi(2)
     }
 }
 
-pub fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
-    let r1 = random_double_unit();
-    let r2 = random_double_unit();
+pub fn random_to_sphere(radius: f64, distance_squared: f64, sampler: &mut dyn Sampler) -> Vec3 {
+    let (r1, r2) = sampler.get_2d();
     let z = 1.0 + r2 * ((1.0 - radius.powi(2) / distance_squared).sqrt() - 1.0);
 
     let phi = 2.0 * PI * r1;
@@ -1,5 +1,3 @@
-use std::f64::INFINITY;
-
 use crate::{
     basic::{
         degrees_to_radians,
@@ -9,6 +7,7 @@ use crate::{
     bvh::aabb::Aabb,
     hittable::{HitRecord, Hittable},
     material::Material,
+    stats::{self, Counter},
 };
 
 #[derive(Clone, Copy)]
@@ -145,6 +144,7 @@ impl<TM: Material> Triangle<TM> {
 impl<TM: Material> Hittable for Triangle<TM> {
     #[allow(clippy::many_single_char_names)]
     fn hit<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, rec: &mut Option<HitRecord<'a>>) -> bool {
+        stats::count(Counter::PrimitiveTests);
         let (hit_point, t) = self.get_hit_point(r);
 
         if t < t_min || t > t_max {
@@ -172,6 +172,7 @@ impl<TM: Material> Hittable for Triangle<TM> {
             u,
             v,
             front_face: Default::default(),
+            object_id: 0,
         };
 
         rec_data.set_face_normal(r, &normal);
@@ -190,8 +191,8 @@ impl<TM: Material> Hittable for Triangle<TM> {
         let mut max_p = Vec3::default();
         for i in 0..3 {
             // x,y,z
-            let mut min = INFINITY;
-            let mut max = -INFINITY;
+            let mut min = f64::INFINITY;
+            let mut max = -f64::INFINITY;
             for j in 0..3 {
                 // three points
                 min = min.min(self.p[j][i]);
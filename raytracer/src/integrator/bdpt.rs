@@ -0,0 +1,501 @@
+use super::emitter::{sample_emission, side_probability};
+use crate::{
+    basic::{
+        ray::Ray,
+        vec3::{dot, Color, Point3, Vec3},
+        PI,
+    },
+    config::config,
+    hittable::{HitRecord, Hittable},
+    pdf::lightable_list::{Lightable, LightableList},
+    sampler::Sampler,
+    stats::{self, Counter},
+    RR_MAX_SURVIVAL, RR_START_DEPTH, SHADOW_EPSILON,
+};
+
+// Bidirectional path tracing (Veach's thesis chapter 10, pbrt-v3 16.3).
+// Light subpaths start on the `Lightable`s and are connected to every vertex of the
+// camera subpath. Strategies with a single camera vertex (splatting light paths onto
+// the film) are not used, so the MIS weights leave them out as well.
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexType {
+    Camera,
+    Light,
+    Surface,
+}
+
+#[derive(Clone)]
+struct Vertex<'a> {
+    kind: VertexType,
+    p: Point3,
+    n: Vec3,
+    // the hit and the ray that found it, only for surface vertices
+    rec: Option<HitRecord<'a>>,
+    r_in: Ray,
+    beta: Color,
+    // radiance leaving the vertex towards the previous one
+    emitted: Color,
+    delta: bool,
+    // area densities of sampling this vertex from its neighbours
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(r: &Ray) -> Self {
+        Vertex {
+            kind: VertexType::Camera,
+            p: r.orig,
+            n: Vec3::default(),
+            rec: None,
+            r_in: *r,
+            beta: Color::new(1.0, 1.0, 1.0),
+            emitted: Color::default(),
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+    fn light(p: Point3, n: Vec3, beta: Color, pdf_pos: f64, tm: f64) -> Self {
+        Vertex {
+            kind: VertexType::Light,
+            p,
+            n,
+            rec: None,
+            r_in: Ray {
+                orig: p,
+                dir: n,
+                tm,
+            },
+            beta,
+            emitted: Color::default(),
+            delta: false,
+            pdf_fwd: pdf_pos,
+            pdf_rev: 0.0,
+        }
+    }
+    fn surface(rec: HitRecord<'a>, r_in: Ray, beta: Color, emitted: Color) -> Self {
+        Vertex {
+            kind: VertexType::Surface,
+            p: rec.p,
+            n: rec.normal,
+            rec: Some(rec),
+            r_in,
+            beta,
+            emitted,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+    fn is_medium(&self) -> bool {
+        match &self.rec {
+            Some(rec) => rec.mat_ptr.is_medium(),
+            None => false,
+        }
+    }
+    // turn a solid angle density at this vertex into an area density at `next`
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let distance_squared = dot(&w, &w);
+        if distance_squared <= 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / distance_squared;
+        if next.kind != VertexType::Camera && !next.is_medium() {
+            pdf *= dot(&next.n, &w).abs() / distance_squared.sqrt();
+        }
+        pdf
+    }
+    // bsdf times cosine from this vertex towards `next`
+    fn eval(&self, next: &Vertex) -> Color {
+        match &self.rec {
+            Some(rec) => rec.mat_ptr.eval(
+                &self.r_in,
+                rec,
+                &Ray {
+                    orig: self.p,
+                    dir: next.p - self.p,
+                    tm: self.r_in.tm,
+                },
+            ),
+            None => Color::default(),
+        }
+    }
+    // area density of scattering towards `next` when this vertex is reached from `prev`
+    fn pdf(&self, prev: &Vertex, next: &Vertex) -> f64 {
+        let rec = match &self.rec {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+        let r_in = Ray {
+            orig: prev.p,
+            dir: self.p - prev.p,
+            tm: self.r_in.tm,
+        };
+        let scattered = Ray {
+            orig: self.p,
+            dir: next.p - self.p,
+            tm: self.r_in.tm,
+        };
+        self.convert_density(rec.mat_ptr.scattering_pdf(&r_in, rec, &scattered), next)
+    }
+    // area density of a light subpath leaving this vertex towards `next`
+    fn pdf_light(&self, next: &Vertex, world: &dyn Hittable) -> f64 {
+        let w = next.p - self.p;
+        let pdf_dir =
+            side_probability(world, &self.p, &self.n, &w) * dot(&self.n, &w.unit_vec()).abs() / PI;
+        self.convert_density(pdf_dir, next)
+    }
+}
+
+// whether nothing blocks the segment, a scattering event in a medium also blocks it
+fn unoccluded(world: &dyn Hittable, from: &Point3, to: &Point3, tm: f64) -> bool {
+    let w = *to - *from;
+    let distance = w.length();
+    stats::count(Counter::SecondaryRays);
+    let mut rec = None;
+    !world.hit(
+        &Ray {
+            orig: *from,
+            dir: w / distance,
+            tm,
+        },
+        0.001,
+        distance * (1.0 - SHADOW_EPSILON),
+        &mut rec,
+    )
+}
+
+// extends `path` by following `ray`, returns the throughput if the walk leaves the scene
+fn random_walk<'a>(
+    world: &'a dyn Hittable,
+    mut ray: Ray,
+    mut beta: Color,
+    mut pdf_fwd: f64,
+    path: &mut Vec<Vertex<'a>>,
+    sampler: &mut dyn Sampler,
+) -> Option<Color> {
+    let beta_start = beta.max_component();
+    // the camera ray is counted where it is made, the path stats are of camera subpaths
+    let camera = path[0].kind == VertexType::Camera;
+
+    for depth in 0..config().max_depth {
+        if depth > 0 || !camera {
+            stats::count(Counter::SecondaryRays);
+        }
+        let mut rec = None;
+        if !world.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+            return Some(beta);
+        }
+        let rec_data = rec.expect("No hit record");
+        if camera {
+            stats::count(Counter::PathVertices);
+        }
+
+        let emitted =
+            rec_data
+                .mat_ptr
+                .emitted(&ray, &rec_data, rec_data.u, rec_data.v, &rec_data.p);
+        let mut vertex = Vertex::surface(rec_data.clone(), ray, beta, emitted);
+        let prev = path.len() - 1;
+        vertex.pdf_fwd = path[prev].convert_density(pdf_fwd, &vertex);
+        path.push(vertex);
+
+        let mut srec = None;
+        if !rec_data
+            .mat_ptr
+            .scatter(&ray, &rec_data, &mut srec, sampler)
+        {
+            break;
+        }
+        let srec_data = srec.expect("No scatter record");
+
+        let pdf_rev = if srec_data.is_specular {
+            path[prev + 1].delta = true;
+            beta = beta * srec_data.attenuation;
+            ray = srec_data.specular_ray;
+            pdf_fwd = 0.0;
+            0.0
+        } else {
+            let bsdf_pdf = srec_data.pdf_func.expect("No pdf function");
+            let scattered = Ray {
+                orig: rec_data.p,
+                dir: bsdf_pdf.generate(sampler),
+                tm: ray.tm,
+            };
+            pdf_fwd = bsdf_pdf.value(&scattered.direction());
+            if pdf_fwd <= 0.0 {
+                break;
+            }
+            beta = beta * rec_data.mat_ptr.eval(&ray, &rec_data, &scattered) / pdf_fwd;
+
+            // density of the walk coming back the way it arrived
+            let back = Ray {
+                orig: rec_data.p,
+                dir: -ray.direction(),
+                tm: ray.tm,
+            };
+            let pdf_back = rec_data
+                .mat_ptr
+                .scattering_pdf(&scattered, &rec_data, &back);
+            ray = scattered;
+            pdf_back
+        };
+        path[prev].pdf_rev = path[prev + 1].convert_density(pdf_rev, &path[prev]);
+
+        // Russian roulette, relative to the throughput the walk started with
+        if depth >= RR_START_DEPTH {
+            let p = (beta.max_component() / beta_start).min(RR_MAX_SURVIVAL);
+            if sampler.get_1d() >= p {
+                break;
+            }
+            beta /= p;
+        }
+        if camera && depth + 1 == config().max_depth {
+            stats::count(Counter::MaxDepthPaths);
+        }
+    }
+
+    None
+}
+
+fn camera_subpath<'a>(
+    r: &Ray,
+    world: &'a dyn Hittable,
+    sampler: &mut dyn Sampler,
+) -> (Vec<Vertex<'a>>, Option<Color>) {
+    let mut path = vec![Vertex::camera(r)];
+    stats::count(Counter::Paths);
+    // the camera density is never needed since there are no t = 1 strategies
+    let escaped = random_walk(
+        world,
+        *r,
+        Color::new(1.0, 1.0, 1.0),
+        1.0,
+        &mut path,
+        sampler,
+    );
+    (path, escaped)
+}
+
+fn light_subpath<'a>(
+    world: &'a dyn Hittable,
+    lights: &LightableList,
+    tm: f64,
+    sampler: &mut dyn Sampler,
+) -> Vec<Vertex<'a>> {
+    let mut path = Vec::new();
+    let emission = match sample_emission(world, lights, sampler) {
+        Some(emission) => emission,
+        None => return path,
+    };
+    path.push(Vertex::light(
+        emission.p,
+        emission.n,
+        emission.le,
+        emission.pdf_pos,
+        tm,
+    ));
+
+    let beta =
+        emission.le * dot(&emission.n, &emission.dir).abs() / (emission.pdf_pos * emission.pdf_dir);
+    let ray = Ray {
+        orig: emission.p,
+        dir: emission.dir,
+        tm,
+    };
+    random_walk(world, ray, beta, emission.pdf_dir, &mut path, sampler);
+    path
+}
+
+// the weight of strategy (s, t) against all the other ways to build the same path
+fn mis_weight(
+    light_path: &[Vertex],
+    camera_path: &[Vertex],
+    sampled: &Option<Vertex>,
+    s: usize,
+    t: usize,
+    world: &dyn Hittable,
+    lights: &LightableList,
+) -> f64 {
+    if s + t == 2 {
+        return 1.0;
+    }
+    let pt = &camera_path[t - 1];
+    let pt_minus = &camera_path[t - 2];
+
+    // emitters missing from the light list can only be found from the camera
+    if s == 0 && lights.point_pdf(&pt.p) <= 0.0 {
+        return 1.0;
+    }
+
+    // the light vertex of the connection, s = 1 uses a freshly sampled one
+    let qs = match s {
+        0 => None,
+        1 => sampled.as_ref(),
+        _ => Some(&light_path[s - 1]),
+    };
+
+    // (pdf_fwd, pdf_rev, delta) of both subpaths, updated for this connection
+    let mut cam: Vec<_> = camera_path[..t]
+        .iter()
+        .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+        .collect();
+    let mut light: Vec<_> = light_path[..s.saturating_sub(1)]
+        .iter()
+        .chain(qs)
+        .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+        .collect();
+
+    cam[t - 1].2 = false;
+    match qs {
+        None => {
+            cam[t - 1].1 = lights.point_pdf(&pt.p);
+            cam[t - 2].1 = pt.pdf_light(pt_minus, world);
+        }
+        Some(qs) => {
+            light[s - 1].2 = false;
+            cam[t - 1].1 = if s == 1 {
+                qs.pdf_light(pt, world)
+            } else {
+                qs.pdf(&light_path[s - 2], pt)
+            };
+            cam[t - 2].1 = pt.pdf(qs, pt_minus);
+            light[s - 1].1 = pt.pdf(pt_minus, qs);
+            if s > 1 {
+                light[s - 2].1 = qs.pdf(pt, &light_path[s - 2]);
+            }
+        }
+    }
+
+    // delta vertices have no density, they only cancel out in the ratios
+    let remap = |f: f64| if f != 0.0 { f } else { 1.0 };
+    let mut sum_ri = 0.0;
+
+    let mut ri = 1.0;
+    for i in (2..t).rev() {
+        ri *= remap(cam[i].1) / remap(cam[i].0);
+        if !cam[i].2 && !cam[i - 1].2 {
+            sum_ri += ri;
+        }
+    }
+
+    ri = 1.0;
+    for i in (0..s).rev() {
+        ri *= remap(light[i].1) / remap(light[i].0);
+        let delta_light = i > 0 && light[i - 1].2;
+        if !light[i].2 && !delta_light {
+            sum_ri += ri;
+        }
+    }
+
+    1.0 / (1.0 + sum_ri)
+}
+
+// contribution of the path made of `s` light and `t` camera vertices
+fn connect(
+    light_path: &[Vertex],
+    camera_path: &[Vertex],
+    s: usize,
+    t: usize,
+    world: &dyn Hittable,
+    lights: &LightableList,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let pt = &camera_path[t - 1];
+    let mut sampled = None;
+
+    let color = if s == 0 {
+        // the camera subpath reached an emitter on its own
+        pt.beta * pt.emitted
+    } else if pt.delta {
+        return Color::default();
+    } else if s == 1 {
+        // a fresh point on a light, like next-event estimation
+        let (p, n) = lights.random_point(sampler);
+        let pdf_pos = lights.point_pdf(&p);
+        if pdf_pos <= 0.0 {
+            return Color::default();
+        }
+
+        let w = p - pt.p;
+        let distance = w.length();
+        let shadow_ray = Ray {
+            orig: pt.p,
+            dir: w / distance,
+            tm: pt.r_in.tm,
+        };
+        stats::count(Counter::SecondaryRays);
+        let mut rec = None;
+        if !world.hit(&shadow_ray, 0.001, f64::INFINITY, &mut rec) {
+            return Color::default();
+        }
+        let rec_data = rec.expect("No hit record");
+        if (rec_data.t - distance).abs() > SHADOW_EPSILON * distance.max(1.0) {
+            return Color::default();
+        }
+        let emitted =
+            rec_data
+                .mat_ptr
+                .emitted(&shadow_ray, &rec_data, rec_data.u, rec_data.v, &rec_data.p);
+
+        let qs = Vertex::light(p, n, emitted / pdf_pos, pdf_pos, pt.r_in.tm);
+        let color = pt.beta * pt.eval(&qs) * qs.beta * dot(&n, &shadow_ray.direction()).abs()
+            / (distance * distance);
+        sampled = Some(qs);
+        color
+    } else {
+        let qs = &light_path[s - 1];
+        if qs.delta {
+            return Color::default();
+        }
+        let w = qs.p - pt.p;
+        let color = qs.beta * qs.eval(pt) * pt.eval(qs) * pt.beta / dot(&w, &w);
+        if color.max_component() <= 0.0 || !unoccluded(world, &pt.p, &qs.p, pt.r_in.tm) {
+            return Color::default();
+        }
+        color
+    };
+
+    if color.max_component() <= 0.0 {
+        return color;
+    }
+    color * mis_weight(light_path, camera_path, &sampled, s, t, world, lights)
+}
+
+pub fn ray_color(
+    r: &Ray,
+    background: &Color,
+    world: &dyn Hittable,
+    lights: &LightableList,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let (camera_path, escaped) = camera_subpath(r, world, sampler);
+    let light_path = light_subpath(world, lights, r.tm, sampler);
+
+    // nothing samples the background but the camera subpath
+    let mut color = match escaped {
+        Some(beta) => beta * *background,
+        None => Color::default(),
+    };
+
+    // s = 1 draws its own light point, so it doesn't need a light subpath
+    let max_s = if lights.is_empty() {
+        0
+    } else {
+        light_path.len().max(1)
+    };
+
+    for t in 2..=camera_path.len() {
+        for s in 0..=max_s {
+            if (s + t - 2) as i32 > config().max_depth {
+                break;
+            }
+            color += connect(&light_path, &camera_path, s, t, world, lights, sampler);
+        }
+    }
+
+    color
+}
@@ -0,0 +1,80 @@
+use crate::{
+    basic::{
+        onb::Onb,
+        vec3::{dot, random_cosine_direction, Color, Point3, Vec3},
+        PI,
+    },
+    hittable::Hittable,
+    pdf::lightable_list::{Lightable, LightableList},
+    sampler::Sampler,
+};
+
+// a ray leaving a random point on the lights, for integrators tracing from the lights
+pub struct Emission {
+    pub p: Point3,
+    pub n: Vec3,
+    pub dir: Vec3,
+    pub le: Color,
+    // area density of `p` and solid angle density of `dir`
+    pub pdf_pos: f64,
+    pub pdf_dir: f64,
+}
+
+// The `Lightable`s only give the shape of the lights, the radiance at a point on them
+// comes from the surface of the world there.
+
+// lights may only emit on one side, so emission picks a side by its brightness
+pub fn side_probability(world: &dyn Hittable, p: &Point3, n: &Vec3, w: &Vec3) -> f64 {
+    let front = world.emitted_at(p, n).max_component();
+    let back = world.emitted_at(p, &-*n).max_component();
+    if front + back <= 0.0 {
+        return 0.0;
+    }
+    if dot(n, w) > 0.0 {
+        front / (front + back)
+    } else {
+        back / (front + back)
+    }
+}
+
+pub fn sample_emission(
+    world: &dyn Hittable,
+    lights: &LightableList,
+    sampler: &mut dyn Sampler,
+) -> Option<Emission> {
+    if lights.is_empty() {
+        return None;
+    }
+
+    let (p, n) = lights.random_point(sampler);
+    let pdf_pos = lights.point_pdf(&p);
+    if pdf_pos <= 0.0 {
+        return None;
+    }
+
+    // choose a side by how much it emits, then a cosine distributed direction on it
+    let front = world.emitted_at(&p, &n).max_component();
+    let back = world.emitted_at(&p, &-n).max_component();
+    if front + back <= 0.0 {
+        return None;
+    }
+    let (side, side_pdf) = if sampler.get_1d() * (front + back) < front {
+        (n, front / (front + back))
+    } else {
+        (-n, back / (front + back))
+    };
+    let dir = Onb::build_from_w(&side).local_by_vec3(random_cosine_direction(sampler));
+    let pdf_dir = side_pdf * dot(&side, &dir).abs() / PI;
+    if pdf_dir <= 0.0 {
+        return None;
+    }
+
+    Some(Emission {
+        p,
+        n,
+        dir,
+        le: world.emitted_at(&p, &dir),
+        pdf_pos,
+        pdf_dir,
+    })
+}
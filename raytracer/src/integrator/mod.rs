@@ -0,0 +1,45 @@
+pub mod bdpt;
+pub mod emitter;
+pub mod path;
+pub mod photon_map;
+pub mod pssmlt;
+pub mod sppm;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    basic::{ray::Ray, vec3::Color},
+    hittable::Hittable,
+    pdf::lightable_list::LightableList,
+    sampler::Sampler,
+};
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integrator {
+    Path,
+    Bdpt,
+    // renders whole iterations at once, see `sppm::create_thread`
+    Sppm,
+    // splats Metropolis chains over the path tracer, see `pssmlt::create_thread`
+    Pssmlt,
+}
+
+impl Integrator {
+    pub fn ray_color(
+        self,
+        r: &Ray,
+        background: &Color,
+        world: &dyn Hittable,
+        lights: &LightableList,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        match self {
+            Integrator::Path => path::ray_color(r, background, world, lights, sampler),
+            Integrator::Bdpt => bdpt::ray_color(r, background, world, lights, sampler),
+            Integrator::Sppm => panic!("Photon mapping has no per-ray estimate"),
+            Integrator::Pssmlt => panic!("Metropolis light transport has no per-ray estimate"),
+        }
+    }
+}
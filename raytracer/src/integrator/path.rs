@@ -0,0 +1,176 @@
+use crate::{
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3},
+    },
+    config::config,
+    hittable::{HitRecord, Hittable},
+    pdf::{
+        lightable_list::{Lightable, LightableList},
+        PDF,
+    },
+    sampler::Sampler,
+    stats::{self, Counter},
+    RR_MAX_SURVIVAL, RR_START_DEPTH, SHADOW_EPSILON,
+};
+
+// next-event estimation: one shadow ray towards a point on a light,
+// weighted against the bsdf sampling that could reach the same light, if any
+pub fn sample_light(
+    r_in: &Ray,
+    rec: &HitRecord,
+    bsdf_pdf: Option<&dyn PDF>,
+    world: &dyn Hittable,
+    lights: &LightableList,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let to_light = lights.random(&rec.p, sampler);
+    let distance = to_light.length();
+    let shadow_ray = Ray {
+        orig: rec.p,
+        dir: to_light / distance,
+        tm: r_in.tm,
+    };
+
+    // the sampled point is unoccluded only if it is the first thing the shadow ray hits
+    stats::count(Counter::SecondaryRays);
+    let mut light_rec = None;
+    if !world.hit(&shadow_ray, 0.001, f64::INFINITY, &mut light_rec) {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let light_rec_data = light_rec.expect("No hit record");
+    if (light_rec_data.t - distance).abs() > SHADOW_EPSILON * distance.max(1.0) {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let light_pdf_val =
+        lights.visible_pdf_value(&rec.p, &shadow_ray.direction(), &light_rec_data.p);
+    if light_pdf_val <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let light_emitted = light_rec_data.mat_ptr.emitted(
+        &shadow_ray,
+        &light_rec_data,
+        light_rec_data.u,
+        light_rec_data.v,
+        &light_rec_data.p,
+    );
+    let weight = match bsdf_pdf {
+        Some(bsdf_pdf) => config()
+            .mis
+            .weight(light_pdf_val, bsdf_pdf.value(&shadow_ray.direction())),
+        None => 1.0,
+    };
+
+    rec.mat_ptr.eval(r_in, rec, &shadow_ray) * light_emitted * weight / light_pdf_val
+}
+
+pub fn ray_color(
+    r: &Ray,
+    background: &Color,
+    world: &dyn Hittable,
+    lights: &LightableList,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let mut color = Color::new(0.0, 0.0, 0.0);
+    // product of bsdf / pdf along the path so far
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = *r;
+    // the vertex and bsdf pdf that sampled `ray`, None for camera and specular rays
+    let mut bsdf_sample: Option<(Point3, f64)> = None;
+
+    // exceed the ray bounce limit, no more light is gathered
+    stats::count(Counter::Paths);
+    for depth in 0..config().max_depth {
+        // the camera ray is counted where it is made
+        if depth > 0 {
+            stats::count(Counter::SecondaryRays);
+        }
+        let mut rec = None;
+
+        // ray hits nothing, gather the background color
+        if !world.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+            color += throughput * *background;
+            break;
+        }
+        stats::count(Counter::PathVertices);
+
+        let rec_data = if let Some(data) = rec {
+            data
+        } else {
+            panic!("No hit record");
+        };
+
+        let emitted =
+            rec_data
+                .mat_ptr
+                .emitted(&ray, &rec_data, rec_data.u, rec_data.v, &rec_data.p);
+
+        // an emitter reached by bsdf sampling is also counted by next-event estimation
+        let weight = match bsdf_sample {
+            Some((o, bsdf_pdf)) if emitted.max_component() > 0.0 => config().mis.weight(
+                bsdf_pdf,
+                lights.visible_pdf_value(&o, &ray.direction(), &rec_data.p),
+            ),
+            _ => 1.0,
+        };
+        color += throughput * emitted * weight;
+
+        let mut srec = None;
+        if !rec_data
+            .mat_ptr
+            .scatter(&ray, &rec_data, &mut srec, sampler)
+        {
+            break;
+        }
+
+        let srec_data = if let Some(data) = srec {
+            data
+        } else {
+            panic!("No scatter record");
+        };
+
+        if srec_data.is_specular {
+            throughput = throughput * srec_data.attenuation;
+            ray = srec_data.specular_ray;
+            bsdf_sample = None;
+        } else {
+            let bsdf_pdf = srec_data.pdf_func.expect("No pdf function");
+
+            if !lights.is_empty() {
+                color += throughput
+                    * sample_light(&ray, &rec_data, Some(&*bsdf_pdf), world, lights, sampler);
+            }
+
+            // bsdf sampling: continues the path
+            let scattered = Ray {
+                orig: rec_data.p,
+                dir: bsdf_pdf.generate(sampler),
+                tm: ray.tm,
+            };
+            let pdf_val = bsdf_pdf.value(&scattered.direction());
+            if pdf_val <= 0.0 {
+                break;
+            }
+
+            throughput = throughput * rec_data.mat_ptr.eval(&ray, &rec_data, &scattered) / pdf_val;
+            bsdf_sample = Some((rec_data.p, pdf_val));
+            ray = scattered;
+        }
+
+        // Russian roulette: survive with probability p and divide by p to stay unbiased
+        if depth >= RR_START_DEPTH {
+            let p = throughput.max_component().min(RR_MAX_SURVIVAL);
+            if sampler.get_1d() >= p {
+                break;
+            }
+            throughput /= p;
+        }
+        if depth + 1 == config().max_depth {
+            stats::count(Counter::MaxDepthPaths);
+        }
+    }
+
+    color
+}
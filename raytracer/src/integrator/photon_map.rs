@@ -0,0 +1,96 @@
+use crate::basic::vec3::{dot, Color, Point3, Vec3};
+
+#[derive(Clone, Copy)]
+pub struct Photon {
+    pub p: Point3,
+    // unit vector towards where the photon came from
+    pub wi: Vec3,
+    pub beta: Color,
+    // splitting axis of the kd-tree node this photon ends up as
+    axis: usize,
+}
+
+impl Photon {
+    pub fn new(p: Point3, wi: Vec3, beta: Color) -> Self {
+        Photon {
+            p,
+            wi,
+            beta,
+            axis: 0,
+        }
+    }
+}
+
+// A balanced kd-tree stored in place: the node of a slice is its median photon,
+// the photons before and after it are the two subtrees.
+#[derive(Default)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+}
+
+impl PhotonMap {
+    pub fn build(mut photons: Vec<Photon>) -> Self {
+        build_node(&mut photons);
+        PhotonMap { photons }
+    }
+    pub fn for_each_within(&self, p: &Point3, radius: f64, f: &mut impl FnMut(&Photon)) {
+        query(&self.photons, p, radius * radius, f);
+    }
+}
+
+fn build_node(photons: &mut [Photon]) {
+    if photons.len() <= 1 {
+        return;
+    }
+
+    // split along the widest extent of the photons
+    let mut minimum = Vec3(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut maximum = Vec3(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY);
+    for photon in photons.iter() {
+        for a in 0..3 {
+            minimum[a] = minimum[a].min(photon.p[a]);
+            maximum[a] = maximum[a].max(photon.p[a]);
+        }
+    }
+    let extent = maximum - minimum;
+    let axis = if extent.0 > extent.1 && extent.0 > extent.2 {
+        0
+    } else if extent.1 > extent.2 {
+        1
+    } else {
+        2
+    };
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| a.p[axis].partial_cmp(&b.p[axis]).unwrap());
+    photons[mid].axis = axis;
+
+    let (left, right) = photons.split_at_mut(mid);
+    build_node(left);
+    build_node(&mut right[1..]);
+}
+
+fn query(photons: &[Photon], p: &Point3, radius_squared: f64, f: &mut impl FnMut(&Photon)) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let mid = photons.len() / 2;
+    let node = &photons[mid];
+    let d = node.p - *p;
+    if dot(&d, &d) <= radius_squared {
+        f(node);
+    }
+
+    // the far side can only hold photons if the splitting plane is within the radius
+    let delta = p[node.axis] - node.p[node.axis];
+    let (near, far) = if delta < 0.0 {
+        (&photons[..mid], &photons[mid + 1..])
+    } else {
+        (&photons[mid + 1..], &photons[..mid])
+    };
+    query(near, p, radius_squared, f);
+    if delta * delta <= radius_squared {
+        query(far, p, radius_squared, f);
+    }
+}
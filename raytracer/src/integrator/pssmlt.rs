@@ -0,0 +1,196 @@
+use std::{
+    sync::{Arc, Barrier, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use indicatif::MultiProgress;
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use super::path;
+use crate::{
+    basic::vec3::Color,
+    config::config,
+    hittable::Hittable,
+    sampler::{hash, mix_bits, primary_sample::PrimarySampler, start_path, Sampler},
+    scenes::SceneOption,
+    stats::{self, Counter},
+    status_bar::Progress,
+    MLT_BOOTSTRAP_SAMPLES, MLT_CHAINS, MLT_LARGE_STEP_PROBABILITY, MLT_SIGMA,
+};
+
+// Primary sample space Metropolis light transport (Kelemen et al. 2002, pbrt-v3 16.4).
+// A path is the path tracer run on a vector of primary samples, including the two that
+// pick its pixel. Markov chains mutate that vector and splat every proposal into the
+// film weighted by its acceptance probability, which makes the film proportional to
+// the image, and a bootstrap estimate of the mean brightness gives the scale.
+
+// splats are summed in fixed point, integer sums do not depend on the order the
+// threads add them in
+const FIXED_POINT_SCALE: f64 = (1_u64 << 32) as f64;
+
+// shared by all threads: the bootstrap weights and one lock per line of the film
+pub struct SplatFilm {
+    barrier: Barrier,
+    // luminance of every bootstrap path, by its index
+    bootstrap: Mutex<Vec<f64>>,
+    lines: Vec<Mutex<Vec<[i64; 3]>>>,
+}
+
+impl SplatFilm {
+    pub fn new() -> Self {
+        SplatFilm {
+            barrier: Barrier::new(config().thread_num as usize),
+            bootstrap: Mutex::new(vec![0.0; MLT_BOOTSTRAP_SAMPLES as usize]),
+            lines: (0..config().image_height)
+                .map(|_| Mutex::new(Vec::new()))
+                .collect(),
+        }
+    }
+    fn splat(&self, (px, py): (u32, u32), color: Color) {
+        let mut line = self.lines[py as usize].lock().unwrap();
+        if line.is_empty() {
+            line.resize(config().image_width as usize, [0; 3]);
+        }
+        for t in 0..3 {
+            let value = (color[t] * FIXED_POINT_SCALE).round() as i64;
+            line[px as usize][t] = line[px as usize][t].saturating_add(value);
+        }
+    }
+}
+
+// one path from the primary samples: the pixel it lands in and its radiance
+fn sample_path(scene: &SceneOption, sampler: &mut dyn Sampler) -> ((u32, u32), Color) {
+    let (x, y) = sampler.get_2d();
+    let x = x * config().image_width as f64;
+    let y = y * config().image_height as f64;
+    let px = (x as u32).min(config().image_width - 1);
+    let py = (y as u32).min(config().image_height - 1);
+    let r = scene.cam.get_ray(
+        x / (config().image_width - 1) as f64,
+        y / (config().image_height - 1) as f64,
+        sampler,
+    );
+    // the media take part in the mutations through the seed of their stream
+    start_path(mix_bits(sampler.get_1d().to_bits()));
+
+    stats::count(Counter::PrimaryRays);
+
+    let mut tmp_rec = None;
+    let hit = scene.world.hit(&r, 0.001, f64::INFINITY, &mut tmp_rec);
+    let mut res = if let (Some(back_img), false) = (&scene.back_img, hit) {
+        back_img.value(px, py)
+    } else {
+        path::ray_color(&r, &scene.background, &scene.world, &scene.lights, sampler)
+    };
+    stats::check_sample(&mut res);
+    ((px, py), res)
+}
+
+// Every thread bootstraps and runs its share of the chains, then returns the lines
+// `id`, `id + thread_num`, ... of the merged film in the layout of `create_thread` in main.
+pub fn create_thread(
+    id: u32,
+    film: Arc<SplatFilm>,
+    scene: Arc<SceneOption>,
+    bars: Arc<MultiProgress>,
+) -> JoinHandle<Vec<(u32, Vec<Color>)>> {
+    thread::spawn(move || {
+        let pixel_count = (config().image_width * config().image_height) as usize;
+        let mutations_per_chain =
+            config().samples_per_pixel as u64 * pixel_count as u64 / MLT_CHAINS as u64;
+        // bootstrap paths and chains are split by index, their seeds never see the threads
+        let chains: Vec<u32> = (id..MLT_CHAINS)
+            .step_by(config().thread_num as usize)
+            .collect();
+
+        // Set Progress Bar for this thread
+        let progress = Progress::thread(&bars, id, chains.len() as u64);
+
+        let sampler = |index: u32| {
+            let seed = hash(&[config().seed, index as u64]);
+            PrimarySampler::new(seed, MLT_SIGMA, MLT_LARGE_STEP_PROBABILITY)
+        };
+
+        // Bootstrap: independent paths, their weights also choose where chains start
+        for index in (id..MLT_BOOTSTRAP_SAMPLES).step_by(config().thread_num as usize) {
+            let (_pixel, color) = sample_path(&scene, &mut sampler(index));
+            film.bootstrap.lock().unwrap()[index as usize] = color.luminance().max(0.0);
+        }
+        film.barrier.wait();
+
+        let mut cdf = Vec::with_capacity(MLT_BOOTSTRAP_SAMPLES as usize);
+        let mut brightness = 0.0;
+        for weight in film.bootstrap.lock().unwrap().iter() {
+            brightness += weight;
+            cdf.push(brightness);
+        }
+        let b = brightness / MLT_BOOTSTRAP_SAMPLES as f64;
+
+        // Markov chains
+        if brightness > 0.0 {
+            for &chain_index in chains.iter() {
+                // picks the start and accepts mutations, apart from the primary samples
+                let mut rng = StdRng::seed_from_u64(hash(&[config().seed, chain_index as u64, 1]));
+                let target = rng.gen::<f64>() * brightness;
+                let index = cdf.partition_point(|&c| c <= target) as u32;
+                let mut chain = sampler(index.min(MLT_BOOTSTRAP_SAMPLES - 1));
+
+                let (mut current_pixel, mut current) = sample_path(&scene, &mut chain);
+                for _mutation in 0..mutations_per_chain {
+                    chain.start_iteration();
+                    let (proposed_pixel, proposed) = sample_path(&scene, &mut chain);
+
+                    let f_current = current.luminance();
+                    let f_proposed = proposed.luminance().max(0.0);
+                    let accept = if f_current > 0.0 {
+                        (f_proposed / f_current).min(1.0)
+                    } else {
+                        1.0
+                    };
+
+                    // expected values: both states contribute by their probability
+                    if accept > 0.0 && f_proposed > 0.0 {
+                        film.splat(proposed_pixel, proposed * (accept / f_proposed));
+                    }
+                    if accept < 1.0 && f_current > 0.0 {
+                        film.splat(current_pixel, current * ((1.0 - accept) / f_current));
+                    }
+
+                    if rng.gen::<f64>() < accept {
+                        current_pixel = proposed_pixel;
+                        current = proposed;
+                        chain.accept();
+                    } else {
+                        chain.reject();
+                    }
+                }
+                progress.inc(chain_index as usize);
+            }
+        }
+        progress.finish();
+        stats::flush();
+
+        film.barrier.wait();
+
+        // scaled by the samples per pixel, since `write_color` averages over the samples
+        let mutations = mutations_per_chain as f64 * MLT_CHAINS as f64;
+        let scale = b * pixel_count as f64 / mutations * config().samples_per_pixel as f64;
+        (id..config().image_height)
+            .step_by(config().thread_num as usize)
+            .map(|py| {
+                let line = film.lines[py as usize].lock().unwrap();
+                let line = if line.is_empty() {
+                    vec![Color::new(0.0, 0.0, 0.0); config().image_width as usize]
+                } else {
+                    line.iter()
+                        .map(|pixel| {
+                            Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64)
+                                * (scale / FIXED_POINT_SCALE)
+                        })
+                        .collect()
+                };
+                (py, line)
+            })
+            .collect()
+    })
+}
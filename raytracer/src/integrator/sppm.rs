@@ -0,0 +1,424 @@
+use std::{
+    sync::{Arc, Barrier, Mutex, RwLock},
+    thread::{self, JoinHandle},
+};
+
+use indicatif::MultiProgress;
+
+use super::{
+    emitter::sample_emission,
+    path::sample_light,
+    photon_map::{Photon, PhotonMap},
+};
+use crate::{
+    basic::{
+        ray::Ray,
+        vec3::{dot, Color},
+        PI,
+    },
+    camera::Camera,
+    config::config,
+    hittable::{HitRecord, Hittable},
+    pdf::lightable_list::LightableList,
+    sampler::{hash, mix_bits, start_path, Sampler, SamplerType},
+    scenes::SceneOption,
+    stats::{self, Counter},
+    status_bar::Progress,
+    RR_MAX_SURVIVAL, RR_START_DEPTH, SPPM_ALPHA, SPPM_INITIAL_RADIUS, SPPM_PHOTONS_PER_PIXEL,
+};
+
+// Stochastic progressive photon mapping (Hachisuka and Jensen 2009, pbrt-v3 16.2).
+// Every iteration each pixel follows one camera ray through specular bounces to a
+// visible point, all threads shoot photons into one shared kd-tree, and each pixel
+// gathers the photons around its visible point while its radius shrinks.
+// Direct lighting at the visible points is sampled from the lights, so photons are
+// only stored after their first bounce, and emitters missing from the light list
+// are only seen directly or through specular bounces.
+
+// where the threads meet to merge their photons once per iteration
+pub struct PhotonExchange {
+    barrier: Barrier,
+    // tagged with the index of the emitted photon, so the map is the same for any thread count
+    photons: Mutex<Vec<(u32, Photon)>>,
+    map: RwLock<PhotonMap>,
+}
+
+impl PhotonExchange {
+    pub fn new() -> Self {
+        PhotonExchange {
+            barrier: Barrier::new(config().thread_num as usize),
+            photons: Mutex::new(Vec::new()),
+            map: RwLock::new(PhotonMap::default()),
+        }
+    }
+}
+
+struct VisiblePoint<'a> {
+    rec: HitRecord<'a>,
+    r_in: Ray,
+    beta: Color,
+}
+
+#[derive(Clone, Copy)]
+struct SppmPixel {
+    radius: f64,
+    // light found directly by the camera paths, summed over the iterations
+    ld: Color,
+    // accumulated photon count and flux within the radius
+    n: f64,
+    tau: Color,
+}
+
+impl SppmPixel {
+    // adds the photons around the visible point and shrinks the radius accordingly
+    fn gather(&mut self, vp: &VisiblePoint, map: &PhotonMap) {
+        let mut phi = Color::new(0.0, 0.0, 0.0);
+        let mut m = 0.0;
+        map.for_each_within(&vp.rec.p, self.radius, &mut |photon| {
+            let wi = Ray {
+                orig: vp.rec.p,
+                dir: photon.wi,
+                tm: vp.r_in.tm,
+            };
+            let mut f = vp.rec.mat_ptr.eval(&vp.r_in, &vp.rec, &wi);
+            // the photon density on a surface already carries the cosine
+            if !vp.rec.mat_ptr.is_medium() {
+                let cosine = dot(&vp.rec.normal, &photon.wi).abs();
+                if cosine <= 0.0 {
+                    return;
+                }
+                f /= cosine;
+            }
+            phi += photon.beta * f;
+            m += 1.0;
+        });
+        self.add_photons(m, vp.beta * phi);
+    }
+
+    // keeps SPPM_ALPHA of the m new photons and shrinks the disc to match, the flux
+    // within it scales with its area so the density estimate stays the same
+    fn add_photons(&mut self, m: f64, flux: Color) {
+        if m > 0.0 {
+            let n = self.n + SPPM_ALPHA * m;
+            let radius = self.radius * (n / (self.n + m)).sqrt();
+            self.tau = (self.tau + flux) * (radius * radius / (self.radius * self.radius));
+            self.n = n;
+            self.radius = radius;
+        }
+    }
+}
+
+// follows the camera ray through specular bounces, returns the light found on the way
+fn trace_camera<'a>(
+    r: &Ray,
+    background: &Color,
+    world: &'a dyn Hittable,
+    lights: &LightableList,
+    sampler: &mut dyn Sampler,
+) -> (Color, Option<VisiblePoint<'a>>) {
+    let mut color = Color::new(0.0, 0.0, 0.0);
+    let mut beta = Color::new(1.0, 1.0, 1.0);
+    let mut ray = *r;
+
+    stats::count(Counter::Paths);
+    for depth in 0..config().max_depth {
+        if depth > 0 {
+            stats::count(Counter::SecondaryRays);
+        }
+        let mut rec = None;
+        if !world.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+            color += beta * *background;
+            break;
+        }
+        let rec_data = rec.expect("No hit record");
+        stats::count(Counter::PathVertices);
+
+        color += beta
+            * rec_data
+                .mat_ptr
+                .emitted(&ray, &rec_data, rec_data.u, rec_data.v, &rec_data.p);
+
+        let mut srec = None;
+        if !rec_data
+            .mat_ptr
+            .scatter(&ray, &rec_data, &mut srec, sampler)
+        {
+            break;
+        }
+        let srec_data = srec.expect("No scatter record");
+
+        if srec_data.is_specular {
+            beta = beta * srec_data.attenuation;
+            ray = srec_data.specular_ray;
+            if depth + 1 == config().max_depth {
+                stats::count(Counter::MaxDepthPaths);
+            }
+            continue;
+        }
+
+        // the camera path ends here, so no bsdf sample competes with the light sample
+        if !lights.is_empty() {
+            color += beta * sample_light(&ray, &rec_data, None, world, lights, sampler);
+        }
+        let vp = VisiblePoint {
+            rec: rec_data,
+            r_in: ray,
+            beta,
+        };
+        return (color, Some(vp));
+    }
+
+    (color, None)
+}
+
+fn photons_per_iteration() -> u32 {
+    SPPM_PHOTONS_PER_PIXEL * config().image_width * config().image_height
+}
+
+// photons `id`, `id + thread_num`, ... of the iteration, each from its own stream
+fn trace_photons(
+    world: &dyn Hittable,
+    lights: &LightableList,
+    cam: &Camera,
+    id: u32,
+    iteration: u32,
+    sampler: &mut dyn Sampler,
+) -> Vec<(u32, Photon)> {
+    let mut photons = Vec::new();
+
+    for index in (id..photons_per_iteration()).step_by(config().thread_num as usize) {
+        sampler.start_pixel_sample(index, 0, iteration);
+        start_path(hash(&[config().seed, index as u64, iteration as u64, 1]));
+        let tm = cam.random_time(sampler);
+        let emission = match sample_emission(world, lights, sampler) {
+            Some(emission) => emission,
+            None => continue,
+        };
+        let beta_start = emission.le * dot(&emission.n, &emission.dir).abs()
+            / (emission.pdf_pos * emission.pdf_dir);
+        let mut beta = beta_start;
+        let mut ray = Ray {
+            orig: emission.p,
+            dir: emission.dir,
+            tm,
+        };
+
+        for depth in 0..config().max_depth {
+            stats::count(Counter::SecondaryRays);
+            let mut rec = None;
+            if !world.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+                break;
+            }
+            let rec_data = rec.expect("No hit record");
+
+            let mut srec = None;
+            if !rec_data
+                .mat_ptr
+                .scatter(&ray, &rec_data, &mut srec, sampler)
+            {
+                break;
+            }
+            let srec_data = srec.expect("No scatter record");
+
+            if srec_data.is_specular {
+                beta = beta * srec_data.attenuation;
+                ray = srec_data.specular_ray;
+            } else {
+                // direct lighting is sampled at the visible points instead
+                if depth > 0 && beta.0.is_finite() && beta.1.is_finite() && beta.2.is_finite() {
+                    photons.push((
+                        index,
+                        Photon::new(rec_data.p, -ray.direction().unit_vec(), beta),
+                    ));
+                }
+
+                let bsdf_pdf = srec_data.pdf_func.expect("No pdf function");
+                let scattered = Ray {
+                    orig: rec_data.p,
+                    dir: bsdf_pdf.generate(sampler),
+                    tm,
+                };
+                let pdf_val = bsdf_pdf.value(&scattered.direction());
+                if pdf_val <= 0.0 {
+                    break;
+                }
+                beta = beta * rec_data.mat_ptr.eval(&ray, &rec_data, &scattered) / pdf_val;
+                ray = scattered;
+            }
+
+            // Russian roulette, relative to the power the photon started with
+            if depth >= RR_START_DEPTH {
+                let p = (beta.max_component() / beta_start.max_component()).min(RR_MAX_SURVIVAL);
+                if sampler.get_1d() >= p {
+                    break;
+                }
+                beta /= p;
+            }
+        }
+    }
+
+    photons
+}
+
+// Every thread keeps the pixels of the lines `id`, `id + thread_num`, ... for all
+// iterations, one iteration per sample, and returns them in the layout of
+// `create_thread` in main.
+pub fn create_thread(
+    id: u32,
+    exchange: Arc<PhotonExchange>,
+    scene: Arc<SceneOption>,
+    bars: Arc<MultiProgress>,
+) -> JoinHandle<Vec<(u32, Vec<Color>)>> {
+    thread::spawn(move || {
+        // Set Progress Bar for this thread
+        let progress = Progress::thread(&bars, id, config().samples_per_pixel as u64);
+
+        let width = config().image_width as usize;
+        let lines: Vec<u32> = (id..config().image_height)
+            .step_by(config().thread_num as usize)
+            .collect();
+        let mut pixels = vec![
+            SppmPixel {
+                radius: SPPM_INITIAL_RADIUS,
+                ld: Color::new(0.0, 0.0, 0.0),
+                n: 0.0,
+                tau: Color::new(0.0, 0.0, 0.0),
+            };
+            lines.len() * width
+        ];
+        let mut sampler = config().sampler.build(config().seed);
+        // a seed of its own, photon `index` must not replay the samples of pixel (index, 0)
+        let mut photon_sampler = SamplerType::Independent.build(mix_bits(config().seed));
+
+        for iteration in 0..config().samples_per_pixel {
+            // Camera pass: one visible point per pixel
+            let mut visible_points = Vec::with_capacity(pixels.len());
+            for (i, &py) in lines.iter().enumerate() {
+                for px in 0..config().image_width {
+                    let pixel = &mut pixels[i * width + px as usize];
+                    sampler.start_pixel_sample(px, py, iteration);
+                    start_path(hash(&[
+                        config().seed,
+                        px as u64,
+                        py as u64,
+                        iteration as u64,
+                    ]));
+                    let (du, dv) = sampler.get_2d();
+                    let u = (px as f64 + du) / (config().image_width - 1) as f64;
+                    let v = (py as f64 + dv) / (config().image_height - 1) as f64;
+                    let r = scene.cam.get_ray(u, v, &mut *sampler);
+                    stats::count(Counter::PrimaryRays);
+
+                    let mut tmp_rec = None;
+                    let hit = scene.world.hit(&r, 0.001, f64::INFINITY, &mut tmp_rec);
+                    if let (Some(back_img), false) = (&scene.back_img, hit) {
+                        pixel.ld += back_img.value(px, py);
+                        visible_points.push(None);
+                        continue;
+                    }
+                    let (mut res, vp) = trace_camera(
+                        &r,
+                        &scene.background,
+                        &scene.world,
+                        &scene.lights,
+                        &mut *sampler,
+                    );
+                    stats::check_sample(&mut res);
+                    pixel.ld += res;
+                    visible_points.push(vp);
+                }
+            }
+
+            // Photon pass: the last thread to arrive builds the shared photon map
+            let photons = trace_photons(
+                &scene.world,
+                &scene.lights,
+                &scene.cam,
+                id,
+                iteration,
+                &mut *photon_sampler,
+            );
+            exchange.photons.lock().unwrap().extend(photons);
+            if exchange.barrier.wait().is_leader() {
+                let mut photons = std::mem::take(&mut *exchange.photons.lock().unwrap());
+                // stable, the bounces of one photon were pushed in order
+                photons.sort_by_key(|&(index, _)| index);
+                *exchange.map.write().unwrap() =
+                    PhotonMap::build(photons.into_iter().map(|(_, photon)| photon).collect());
+            }
+            exchange.barrier.wait();
+
+            // Gather pass
+            let map = exchange.map.read().unwrap();
+            for (pixel, vp) in pixels.iter_mut().zip(visible_points.iter()) {
+                if let Some(vp) = vp {
+                    pixel.gather(vp, &map);
+                }
+            }
+            std::mem::drop(map);
+
+            progress.inc(iteration as usize);
+        }
+        progress.finish();
+        stats::flush();
+
+        // scaled by the samples per pixel, since `write_color` averages over the samples
+        let photons_emitted = photons_per_iteration() as f64 * config().samples_per_pixel as f64;
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, &py)| {
+                let line = pixels[i * width..(i + 1) * width]
+                    .iter()
+                    .map(|pixel| {
+                        pixel.ld
+                            + pixel.tau * config().samples_per_pixel as f64
+                                / (photons_emitted * PI * pixel.radius * pixel.radius)
+                    })
+                    .collect();
+                (py, line)
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel() -> SppmPixel {
+        SppmPixel {
+            radius: 1.0,
+            ld: Color::new(0.0, 0.0, 0.0),
+            n: 0.0,
+            tau: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn no_photons_keep_the_radius() {
+        let mut p = pixel();
+        p.add_photons(0.0, Color::new(0.0, 0.0, 0.0));
+        assert_eq!(p.radius, 1.0);
+        assert_eq!(p.n, 0.0);
+    }
+
+    #[test]
+    fn radius_shrinks_by_alpha() {
+        let mut p = pixel();
+        p.add_photons(10.0, Color::new(3.0, 3.0, 3.0));
+        assert!((p.n - SPPM_ALPHA * 10.0).abs() < 1e-12);
+        assert!((p.radius * p.radius - SPPM_ALPHA).abs() < 1e-12);
+        assert!((p.tau.0 - 3.0 * SPPM_ALPHA).abs() < 1e-12);
+
+        for _iteration in 0..1000 {
+            let (radius, n) = (p.radius, p.n);
+            let density = (p.tau.0 + 3.0) / (radius * radius);
+            p.add_photons(10.0, Color::new(3.0, 3.0, 3.0));
+            assert!(p.radius < radius && p.radius > 0.0);
+            assert!(p.n > n);
+            // the flux within the smaller disc has the same density
+            assert!((p.tau.0 / (p.radius * p.radius) - density).abs() < 1e-9 * density);
+        }
+    }
+}
@@ -1,284 +1,455 @@
 use std::{
-    fs::File,
     process::exit,
-    sync::{Arc, Mutex},
-    thread::{self, JoinHandle},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    basic::background::Background,
-    scenes::final_scene::final_scene,
-    status_bar::{show_image_information, show_thread_information},
-    texture::image_texture::ImageTexture,
+    adaptive::{AdaptiveFilm, PixelEstimate},
+    aov::Features,
+    checkpoint::Checkpoint,
+    config::config,
+    output::ImageFormat,
+    scenes::{scene_by_name, SceneOption},
+    stats::Counter,
+    status_bar::{
+        show_checkpoint_saved, show_finished, show_image_information, show_note, show_output,
+        show_thread_information, show_time_limit_reached, Progress,
+    },
+    tile::Tile,
 };
-use basic::{clamp, random_double_unit, ray::Ray, vec3::Color, INFINITY};
-use camera::Camera;
+use basic::{clamp, vec3::Color};
 use console::style;
-use hittable::{hittable_list::HittableList, Hittable};
-use image::{ImageBuffer, Rgb, RgbImage};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use pdf::{
-    hittable_pdf::HittablePDF, lightable_list::Lightable, lightable_list::LightableList,
-    mixture_pdf::MixturePDF, PDF,
-};
-
+use hittable::Hittable;
+use image::{DynamicImage, ImageBuffer, Pixel, Rgb};
+use indicatif::MultiProgress;
+use integrator::{pssmlt, sppm, Integrator};
+use sampler::{hash, start_path};
+
+mod adaptive;
+mod aov;
 mod basic;
 mod bvh;
 mod camera;
+mod checkpoint;
+mod config;
+mod denoise;
+mod distributed;
+mod filter;
 mod hittable;
+mod integrator;
 mod material;
 mod obj_loader;
+mod output;
 mod pdf;
+mod sampler;
 mod scenes;
+mod stats;
 mod status_bar;
 mod texture;
+mod tile;
+mod tonemap;
+
+// Image size, samples, depth, threads, output and scene are set at runtime, see config.rs
+
+// Adaptive sampling, see --adaptive
+const ADAPTIVE_MIN_SAMPLES: u32 = 64;
+// at most this many times the samples per pixel in one pixel
+const ADAPTIVE_MAX_RATIO: u32 = 4;
+const ADAPTIVE_BATCH: u32 = 64;
+// relative standard error of the luminance at which a pixel stops
+const ADAPTIVE_ERROR: f64 = 0.01;
+// samples per pixel of every pass after the first with a --time-limit
+const TIME_LIMIT_BATCH: u32 = 16;
+
+// Russian roulette
+const RR_START_DEPTH: i32 = 3;
+const RR_MAX_SURVIVAL: f64 = 0.95;
+
+// offset of the shadow rays of the light sampling
+const SHADOW_EPSILON: f64 = 1e-4;
+
+// Stochastic progressive photon mapping, one iteration per sample
+const SPPM_PHOTONS_PER_PIXEL: u32 = 1;
+const SPPM_INITIAL_RADIUS: f64 = 2.0;
+const SPPM_ALPHA: f64 = 2.0 / 3.0;
+
+// Primary sample space Metropolis light transport, as many mutations per pixel as samples
+const MLT_BOOTSTRAP_SAMPLES: u32 = 100_000;
+const MLT_CHAINS: u32 = 1000;
+const MLT_SIGMA: f64 = 0.01;
+const MLT_LARGE_STEP_PROBABILITY: f64 = 0.3;
+
+// Tiles, rendered by a work-stealing thread pool
+const TILE_SIZE: u32 = 32;
+
+// Denoiser, see denoise.rs
+const DENOISE_ITERATIONS: u32 = 5;
+const DENOISE_SIGMA_LUMINANCE: f64 = 4.0;
+const DENOISE_SIGMA_NORMAL: f64 = 128.0;
+// relative to the depth, per pixel of the kernel step
+const DENOISE_SIGMA_DEPTH: f64 = 0.02;
+const DENOISE_SIGMA_ALBEDO: f64 = 0.1;
+
+// Checkpoints, see checkpoint.rs
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(600);
+
+// averaged over the samples per pixel, through the display transform and clamped to [0, 1)
+fn display_color(pixel_colors: &Color) -> Color {
+    let c = tonemap::display(*pixel_colors / (config().samples_per_pixel as f64));
+    Color::new(
+        clamp(c.0, 0.0, 0.999),
+        clamp(c.1, 0.0, 0.999),
+        clamp(c.2, 0.0, 0.999),
+    )
+}
 
-// Image
-const ASPECT_RATIO: f64 = 16.0 / 10.0;
-const IMAGE_WIDTH: u32 = 2560;
-const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-const SAMPLES_PER_PIXEL: u32 = 8000;
-const MAX_DEPTH: i32 = 60;
-
-// Threads
-const THREAD_NUM: u32 = 20;
-const LINES_PER_ISSUE: u32 = 1;
-
-fn ray_color(
-    r: &Ray,
-    background: &Color,
-    world: &dyn Hittable,
-    lights: &dyn Lightable,
-    depth: i32,
-) -> Color {
-    let mut rec = None;
-
-    // exceed the ray bounce limit, no more light is gathered
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
+fn write_color(pixel: &mut Rgb<u8>, pixel_colors: &Color) {
+    let c = display_color(pixel_colors);
+    let r = (c.0 * (256_f64)).floor() as u8;
+    let g = (c.1 * (256_f64)).floor() as u8;
+    let b = (c.2 * (256_f64)).floor() as u8;
 
-    // ray hits nothing, return the background color
-    if !world.hit(r, 0.001, INFINITY, &mut rec) {
-        return *background;
-    }
+    *pixel = image::Rgb([r, g, b]);
+}
 
-    let mut srec = None;
+fn write_color_16(pixel: &mut Rgb<u16>, pixel_colors: &Color) {
+    let c = display_color(pixel_colors);
+    let r = (c.0 * (65536_f64)).floor() as u16;
+    let g = (c.1 * (65536_f64)).floor() as u16;
+    let b = (c.2 * (65536_f64)).floor() as u16;
 
-    let rec_data = if let Some(data) = rec {
-        data
-    } else {
-        panic!("No hit record");
-    };
+    *pixel = image::Rgb([r, g, b]);
+}
 
-    let emitted = rec_data
-        .mat_ptr
-        .emitted(&r, &rec_data, rec_data.u, rec_data.v, &rec_data.p);
+fn fill_image<P: Pixel + 'static>(
+    lines: &[(u32, Vec<Color>)],
+    write_color: fn(&mut P, &Color),
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let mut img = ImageBuffer::new(config().image_width, config().image_height);
+    for (py, line) in lines {
+        for px in 0..config().image_width {
+            let pixel = img.get_pixel_mut(px, config().image_height - py - 1);
+            write_color(pixel, &line[px as usize]);
+        }
+    }
+    img
+}
 
-    if !rec_data.mat_ptr.scatter(r, &rec_data, &mut srec) {
-        return emitted;
+// the linear radiance for the HDR formats, top row first
+fn radiance(lines: &[(u32, Vec<Color>)]) -> Vec<Color> {
+    let width = config().image_width as usize;
+    let mut radiance = vec![Color::default(); width * config().image_height as usize];
+    for (py, line) in lines {
+        let row = (config().image_height - py - 1) as usize;
+        for (pixel, color) in radiance[row * width..(row + 1) * width]
+            .iter_mut()
+            .zip(line)
+        {
+            *pixel = *color / config().samples_per_pixel as f64;
+        }
     }
+    radiance
+}
 
-    let srec_data = if let Some(data) = srec {
-        data
+// in the format of the extension of `path`
+fn output_image(path: &str, lines: &[(u32, Vec<Color>)]) {
+    let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Jpeg);
+    let result = if format.is_hdr() {
+        output::write_hdr(path, format, &output::crop(&radiance(lines)))
+    } else if format == ImageFormat::Png && config().png_16bit {
+        let img = DynamicImage::ImageRgb16(fill_image(lines, write_color_16));
+        output::write_ldr(path, format, &output::crop_image(img))
     } else {
-        panic!("No scatter record");
+        let img = DynamicImage::ImageRgb8(fill_image(lines, write_color));
+        output::write_ldr(path, format, &output::crop_image(img))
     };
+    show_output("image", path, result);
+}
 
-    if srec_data.is_specular {
-        return srec_data.attenuation
-            * ray_color(
-                &srec_data.specular_ray,
-                background,
-                world,
-                lights,
-                depth - 1,
-            );
+// Renders one pass of `film` inside the tile, on a copy of its pixels that is
+// written back as a whole when the tile is done, false if it stopped before
+fn render_tile(index: usize, tile: &Tile, film: &AdaptiveFilm, scene: &SceneOption) -> bool {
+    let mut sampler = config().sampler.build(config().seed);
+    let width = (tile.x1 - tile.x0) as usize;
+    let mut block: Vec<PixelEstimate> = (tile.y0..tile.y1)
+        .flat_map(|py| {
+            film.lines[py as usize].lock().unwrap()[tile.x0 as usize..tile.x1 as usize].to_vec()
+        })
+        .collect();
+    let mut splats = film.splats[index].lock().unwrap().clone();
+
+    for py in tile.y0..tile.y1 {
+        for px in tile.x0..tile.x1 {
+            let pixel = &mut block[(py - tile.y0) as usize * width + (px - tile.x0) as usize];
+            // a time limit samples every pixel alike
+            if film.pass > 0
+                && config().adaptive
+                && config().time_limit.is_none()
+                && pixel.converged()
+            {
+                continue;
+            }
+            if checkpoint::interrupted() || film.time_up() {
+                return false;
+            }
+            for s in pixel.count..pixel.count + film.batch {
+                sampler.start_pixel_sample(px, py, s);
+                start_path(hash(&[config().seed, px as u64, py as u64, s as u64]));
+                // a bunch of rays hitting the object
+                let (du, dv) = sampler.get_2d();
+                let (x, y) = (px as f64 + du, py as f64 + dv);
+                let u = x / (config().image_width - 1) as f64;
+                let v = y / (config().image_height - 1) as f64;
+                let r = scene.cam.get_ray(u, v, &mut *sampler);
+                stats::count(Counter::PrimaryRays);
+
+                let mut tmp_rec = None;
+                let hit = scene.world.hit(&r, 0.001, f64::INFINITY, &mut tmp_rec);
+                let background = match &scene.back_img {
+                    Some(back_img) => back_img.value(px, py),
+                    None => scene.background,
+                };
+                pixel
+                    .features
+                    .add(&Features::first_hit(&r, &tmp_rec, background));
+                let color = if let (Some(back_img), false) = (&scene.back_img, hit) {
+                    back_img.value(px, py)
+                } else {
+                    let mut res = config().integrator.ray_color(
+                        &r,
+                        &scene.background,
+                        &scene.world,
+                        &scene.lights,
+                        &mut *sampler,
+                    );
+                    stats::check_sample(&mut res);
+                    res
+                };
+                pixel.add(color);
+                splats.add(x, y, color);
+            }
+        }
     }
 
-    let light_pdf = Box::new(HittablePDF {
-        o: rec_data.p,
-        ptr: lights,
-    });
-    let mixed_pdf = MixturePDF::new(light_pdf, srec_data.pdf_func.expect("No pdf function"));
-    let scattered = Ray {
-        orig: rec_data.p,
-        dir: mixed_pdf.generate(),
-        tm: r.tm,
-    };
-
-    let pdf_val = mixed_pdf.value(&scattered.direction());
-
-    emitted
-        + srec_data.attenuation
-            * rec_data.mat_ptr.scattering_pdf(r, &rec_data, &scattered)
-            * ray_color(&scattered, background, world, lights, depth - 1)
-            / pdf_val
+    stats::flush();
+    let mut done = film.done.lock().unwrap();
+    for (py, row) in (tile.y0..tile.y1).zip(block.chunks(width)) {
+        film.lines[py as usize].lock().unwrap()[tile.x0 as usize..tile.x1 as usize]
+            .copy_from_slice(row);
+    }
+    *film.splats[index].lock().unwrap() = splats;
+    done[index] = true;
+    true
 }
 
-fn write_color(pixel: &mut Rgb<u8>, pixel_colors: &Color) {
-    let mut r = pixel_colors.0 / (SAMPLES_PER_PIXEL as f64);
-    let mut g = pixel_colors.1 / (SAMPLES_PER_PIXEL as f64);
-    let mut b = pixel_colors.2 / (SAMPLES_PER_PIXEL as f64);
-
-    // Gamma-correct for gamma=2.0
-    r = r.sqrt();
-    g = g.sqrt();
-    b = b.sqrt();
-
-    let r = (clamp(r, 0.0, 0.999) * (256_f64)).floor() as u8;
-    let g = (clamp(g, 0.0, 0.999) * (256_f64)).floor() as u8;
-    let b = (clamp(b, 0.0, 0.999) * (256_f64)).floor() as u8;
-
-    //println!("{} {} {}", r, g, b);
+// Every pass queues all tiles in --tile-order on a rayon pool, whose threads steal
+// the next tile as soon as they are idle
+fn render_tiles(scene: &SceneOption) -> AdaptiveFilm {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config().thread_num as usize)
+        .build()
+        .unwrap();
+    let mut film = match (&config().checkpoint, config().resume) {
+        (Some(path), true) => checkpoint::load(path),
+        _ => AdaptiveFilm::new(),
+    };
+    let tiles = film.tiles.clone();
+    let checkpoint = config().checkpoint.as_deref().map(Checkpoint::new);
+    film.deadline = config().time_limit.map(|limit| Instant::now() + limit);
+
+    let tile_num = (0..tiles.len())
+        .filter(|&index| distributed::owns(index))
+        .count();
+    let progress = Progress::tiles(tile_num as u64);
+
+    while film.batch > 0 {
+        let done = film.done.get_mut().unwrap().clone();
+        let done_num = (0..tiles.len()).filter(|&index| done[index] && distributed::owns(index));
+        progress.start_pass(film.pass, done_num.count() as u64);
+        pool.scope_fifo(|s| {
+            let todo = tiles
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| distributed::owns(index) && !done[index]);
+            for (index, tile) in todo {
+                let (film, progress, checkpoint) = (&film, &progress, &checkpoint);
+                s.spawn_fifo(move |_| {
+                    if !render_tile(index, tile, film, scene) {
+                        return;
+                    }
+                    progress.inc(index);
+                    if let Some(checkpoint) = checkpoint {
+                        checkpoint.tick(film);
+                    }
+                });
+            }
+        });
+
+        // Ctrl-C: keep what is done and stop
+        if let (Some(checkpoint), true) = (&checkpoint, checkpoint::interrupted()) {
+            checkpoint.save(&film);
+            progress.abandon();
+            show_checkpoint_saved(config().checkpoint.as_deref().unwrap());
+            exit(130);
+        }
+        // the tiles left of this pass keep the samples of the passes before
+        if film.time_up() {
+            if let Some(checkpoint) = &checkpoint {
+                checkpoint.save(&film);
+            }
+            progress.finish_pass(film.pass, 0);
+            film.batch = 0;
+            break;
+        }
+        film.next_pass();
+        progress.finish_pass(film.pass - 1, film.batch);
 
-    *pixel = image::Rgb([r, g, b]);
+        // progressive mode: the image so far, unless this was the last pass
+        if let Some(checkpoint) = &checkpoint {
+            checkpoint.save(&film);
+        }
+        if config().progressive_spp.is_some() && config().worker.is_none() && film.batch > 0 {
+            output_image(&config().output, &image_colors(&film));
+        }
+    }
+    progress.finish();
+    if config().time_limit.is_some() {
+        let (mean, min, max) = film.sample_counts();
+        show_time_limit_reached(mean, min, max);
+    }
+    film
 }
 
-fn output_image(path: &str, img: &RgbImage, quality: u8) {
-    println!("Ouput image as \"{}\"", style(path).yellow());
-    let output_image = image::DynamicImage::ImageRgb8(img.clone());
-    let mut output_file = File::create(path).unwrap();
-    match output_image.write_to(&mut output_file, image::ImageOutputFormat::Jpeg(quality)) {
-        Ok(_) => {}
-        // Err(_) => panic!("Outputting image fails."),
-        Err(_) => println!("{}", style("Outputting image fails.").red()),
+// the colors of the tile renderer for the image, denoised if asked to
+fn image_colors(film: &AdaptiveFilm) -> Vec<(u32, Vec<Color>)> {
+    let colors = film.colors();
+    if config().denoise {
+        denoise::denoise(&colors, film)
+    } else {
+        colors
     }
 }
 
-fn create_thread(
-    line_pool: Arc<Mutex<u32>>,
-    world: HittableList,
-    lights: LightableList,
-    background: Color,
-    back_img: Background,
-    cam: Camera,
-    bars: Arc<MultiProgress>,
-) -> JoinHandle<Vec<(u32, Vec<Color>)>> {
-    let mut ret = Vec::<_>::new();
-    thread::spawn(move || {
-        // Set Progress Bar for this thread
-        let now_bar = bars.add(ProgressBar::new((IMAGE_HEIGHT / THREAD_NUM) as u64));
-        now_bar.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] [{pos}/{len}] ({eta})")
-        .progress_chars("#>-"));
-
-        // Catch one avaliable line
-        loop {
-            let mut num = line_pool.lock().unwrap();
-            if *num >= IMAGE_HEIGHT {
-                break;
-            }
-            let py0 = *num;
-            let py1 = if py0 + LINES_PER_ISSUE <= IMAGE_HEIGHT {
-                py0 + LINES_PER_ISSUE
-            } else {
-                IMAGE_HEIGHT
-            };
-            *num += LINES_PER_ISSUE;
-            std::mem::drop(num);
-
-            for py in py0..py1 {
-                now_bar.inc(1);
-
-                let mut line_color = Vec::<Color>::new();
-
-                for px in 0..IMAGE_WIDTH {
-                    let mut pixel_colors = Color::new(0.0, 0.0, 0.0);
-                    for _s in 0..SAMPLES_PER_PIXEL {
-                        // a bunch of rays hitting the object
-                        let u = (px as f64 + random_double_unit()) / (IMAGE_WIDTH - 1) as f64;
-                        let v = (py as f64 + random_double_unit()) / (IMAGE_HEIGHT - 1) as f64;
-                        let r = cam.get_ray(u, v);
-
-                        let mut tmp_rec = None;
-                        if !world.hit(&r, 0.001, INFINITY, &mut tmp_rec) {
-                            pixel_colors += back_img.value(px, py);
-                        } else {
-                            let mut res = ray_color(&r, &background, &world, &lights, MAX_DEPTH);
-                            for t in 0..3 {
-                                if res[t].is_nan() {
-                                    res[t] = 0.0;
-                                }
-                            }
-                            pixel_colors += res;
-                        }
-                    }
-                    line_color.push(pixel_colors);
-                }
-                ret.push((py, line_color));
+// The integrators that render whole iterations at once run one thread each,
+// which returns the lines `id`, `id + config().thread_num`, ...
+fn render_threads(scene: Arc<SceneOption>) -> Vec<(u32, Vec<Color>)> {
+    let multiprogress = Arc::new(MultiProgress::new());
+    multiprogress.set_move_cursor(true);
+
+    let exchange = Arc::new(sppm::PhotonExchange::new());
+    let film = Arc::new(pssmlt::SplatFilm::new());
+    let mut thread_list = Vec::<_>::new();
+    for id in 0..config().thread_num {
+        thread_list.push(match config().integrator {
+            Integrator::Sppm => {
+                sppm::create_thread(id, exchange.clone(), scene.clone(), multiprogress.clone())
             }
+            _ => pssmlt::create_thread(id, film.clone(), scene.clone(), multiprogress.clone()),
+        });
+    }
+    multiprogress.join().unwrap();
+
+    let mut lines = Vec::new();
+    for (id, thread) in thread_list.into_iter().enumerate() {
+        match thread.join() {
+            Ok(res) => lines.extend(res),
+            Err(_) => eprintln!("Thread Failed!!! {}", id),
         }
-        now_bar.finish_with_message("Finished.");
-        ret
-    })
+    }
+    lines
 }
 
 fn main() {
+    let cfg = config::load();
+    let scene = match scene_by_name(&cfg.scene) {
+        Some(scene) => scene,
+        None => {
+            eprintln!("{} {}", style("Unknown scene").red(), cfg.scene);
+            exit(2);
+        }
+    };
+
     // Output Path
-    let path = "output/works/final_scene.jpg";
+    let path = cfg.output.as_str();
 
     // Show the Image Information
     show_image_information(path);
 
-    // Threads
-    let mut thread_list = Vec::<_>::new();
-    let line_pool = Arc::new(Mutex::new(0_u32));
-
-    // Threads: progress bar
-    let multiprogress = Arc::new(MultiProgress::new());
-    multiprogress.set_move_cursor(true);
+    // Distributed rendering splits the tiles
+    let distributed = cfg.workers.is_some() || cfg.worker.is_some();
+    let tiled = distributed
+        || cfg.denoise
+        || !cfg.aovs.is_empty()
+        || cfg.time_limit.is_some()
+        || cfg.crop.is_some();
+    if tiled && matches!(cfg.integrator, Integrator::Sppm | Integrator::Pssmlt) {
+        eprintln!(
+            "Only path and BDPT render distributed, denoised, cropped, with AOVs or a time limit"
+        );
+        exit(2);
+    }
 
     // Show the Threads Information
     show_thread_information();
 
-    let stars = Background {
-        img: Arc::new(ImageTexture::load_image_file(
-            "./raytracer/sources/Images/background.jpg",
-        )),
+    // Multi-Thread, in worker processes or merged from their parts
+    let start = Instant::now();
+    let (lines, adaptive_film) = if let Some(num) = cfg.workers {
+        let film = distributed::run_workers(num);
+        (image_colors(&film), Some(film))
+    } else if !cfg.merge.is_empty() {
+        let film = distributed::merge(&cfg.merge);
+        (image_colors(&film), Some(film))
+    } else {
+        // Scene, built once and shared by all threads
+        let scene = Arc::new(scene());
+        match config().integrator {
+            Integrator::Sppm | Integrator::Pssmlt => {
+                let lines = render_threads(scene);
+                stats::rendered(start.elapsed());
+                (lines, None)
+            }
+            _ => {
+                let film = render_tiles(&scene);
+                stats::rendered(start.elapsed());
+                if let Some((index, _)) = cfg.worker {
+                    distributed::write_part(index, &film);
+                    exit(0);
+                }
+                (image_colors(&film), Some(film))
+            }
+        }
     };
-    // Multi-Thread
-    for id in 0..THREAD_NUM {
-        let scene_op = final_scene(id);
-        thread_list.push(create_thread(
-            line_pool.clone(),
-            scene_op.world,
-            scene_op.lights,
-            scene_op.background,
-            stars.clone(),
-            scene_op.cam,
-            multiprogress.clone(),
-        ));
-    }
-
-    multiprogress.join().unwrap();
+    let render_seconds = start.elapsed().as_secs_f64();
 
     // Generating Image
-    let quality = 60;
-    let mut img: RgbImage = ImageBuffer::new(IMAGE_WIDTH, IMAGE_HEIGHT);
-
-    // Generating Image: Progress Bar
-    let generating_progress_bar = ProgressBar::new(IMAGE_HEIGHT as u64);
-    println!("🚛 {}", style("Filling up Pixels...").green(),);
-
-    for _id in 0..THREAD_NUM {
-        match thread_list.remove(0).join() {
-            Ok(res) => {
-                for line in res {
-                    let py = line.0;
-                    for px in 0..IMAGE_WIDTH {
-                        let pixel = img.get_pixel_mut(px, IMAGE_HEIGHT - py - 1);
-                        write_color(pixel, &line.1[px as usize]);
-                    }
-                    generating_progress_bar.inc(1);
-                }
+    show_note(&format!("🚛 {}", style("Filling up Pixels...").green()));
+    // the AOVs go into an .exr output, next to the others
+    match &adaptive_film {
+        Some(film) if !cfg.aovs.is_empty() && path.to_ascii_lowercase().ends_with(".exr") => {
+            aov::write_exr(path, &output::crop(&radiance(&lines)), film)
+        }
+        _ => {
+            output_image(path, &lines);
+            if let Some(film) = &adaptive_film {
+                aov::write_images(path, film);
             }
-            Err(_) => println!("Thread Failed!!! {}", _id),
         }
     }
-    generating_progress_bar.finish();
 
-    output_image(path, &img, quality);
+    if let (true, true, Some(adaptive_film)) =
+        (config().sample_heatmap, config().adaptive, &adaptive_film)
+    {
+        let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+        let heatmap_path = format!("{}_samples.jpg", stem);
+        let heatmap = output::crop_image(DynamicImage::ImageRgb8(adaptive_film.heatmap()));
+        let result = output::write_ldr(&heatmap_path, ImageFormat::Jpeg, &heatmap);
+        show_output("heatmap", &heatmap_path, result);
+    }
+
+    stats::report(path);
+    show_finished(render_seconds);
 
     exit(0);
 }
@@ -1,10 +1,10 @@
 use crate::{
     basic::{
-        random_double_unit,
         ray::Ray,
         vec3::{dot, reflect, refract, Vec3},
     },
     hittable::HitRecord,
+    sampler::Sampler,
 };
 
 use super::{Material, ScatterRecord};
@@ -23,7 +23,13 @@ impl Dielectric {
 
 impl Material for Dielectric {
     #[allow(unused_variables)]
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut Option<ScatterRecord>) -> bool {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut Option<ScatterRecord>,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
         } else {
@@ -36,7 +42,7 @@ impl Material for Dielectric {
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > random_double_unit()
+            || Dielectric::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
         {
             reflect(&unit_direction, &rec.normal)
         } else {
@@ -1,9 +1,10 @@
+use std::f64::consts::PI;
+
 use crate::{
-    basic::{
-        ray::Ray,
-        vec3::{random_in_unit_sphere, Color},
-    },
+    basic::{ray::Ray, vec3::Color},
     hittable::HitRecord,
+    pdf::sphere_pdf::SpherePDF,
+    sampler::Sampler,
     texture::{solid_color_texture::SolidColor, Texture},
 };
 
@@ -27,18 +28,32 @@ impl Isotropic {
 
 impl Material for Isotropic {
     #[allow(unused_variables)]
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut Option<ScatterRecord>) -> bool {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut Option<ScatterRecord>,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         *srec = Some(ScatterRecord {
-            specular_ray: Ray {
-                orig: rec.p,
-                dir: random_in_unit_sphere(),
-                tm: r_in.tm,
-            },
-
+            specular_ray: Default::default(),
             is_specular: false,
             attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
-            pdf_func: None,
+            pdf_func: Some(Box::new(SpherePDF)),
         });
         true
     }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(r_in, rec, scattered)
+    }
+    #[allow(unused_variables)]
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+    fn is_medium(&self) -> bool {
+        true
+    }
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 }
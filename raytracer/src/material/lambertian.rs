@@ -7,6 +7,7 @@ use crate::{
     },
     hittable::HitRecord,
     pdf::cos_pdf::CosPDF,
+    sampler::Sampler,
     texture::{solid_color_texture::SolidColor, Texture},
 };
 
@@ -38,11 +39,12 @@ impl<TT> Material for Lambertian<TT>
 where
     TT: Texture,
 {
-    fn scatter<'a>(
+    fn scatter(
         &self,
         _r_in: &Ray,
         rec: &HitRecord,
-        srec: &'a mut Option<ScatterRecord>,
+        srec: &mut Option<ScatterRecord>,
+        _sampler: &mut dyn Sampler,
     ) -> bool {
         *srec = Some(ScatterRecord {
             specular_ray: Default::default(),
@@ -52,6 +54,9 @@ where
         });
         true
     }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p) * self.scattering_pdf(r_in, rec, scattered)
+    }
     #[allow(unused_variables)]
     fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
         let cosine = dot(&rec.normal, &scattered.dir.unit_vec());
@@ -61,4 +66,7 @@ where
             cosine / PI
         }
     }
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 }
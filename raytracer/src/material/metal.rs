@@ -4,6 +4,7 @@ use crate::{
         vec3::{random_in_unit_sphere, reflect, Color},
     },
     hittable::HitRecord,
+    sampler::Sampler,
 };
 
 use super::{Material, ScatterRecord};
@@ -16,12 +17,18 @@ pub struct Metal {
 
 impl Material for Metal {
     #[allow(unused_variables)]
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut Option<ScatterRecord>) -> bool {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut Option<ScatterRecord>,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         let reflected = reflect(&r_in.direction().unit_vec(), &rec.normal);
         *srec = Some(ScatterRecord {
             specular_ray: Ray {
                 orig: rec.p,
-                dir: reflected + random_in_unit_sphere() * self.fuzz.min(1.0),
+                dir: reflected + random_in_unit_sphere(sampler) * self.fuzz.min(1.0),
                 tm: r_in.tm,
             },
             is_specular: true,
@@ -30,4 +37,8 @@ impl Material for Metal {
         });
         true
     }
+    #[allow(unused_variables)]
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo
+    }
 }
@@ -3,6 +3,9 @@ pub mod diffuse_light;
 pub mod isotropic;
 pub mod lambertian;
 pub mod metal;
+pub mod tagged;
+
+use std::sync::Arc;
 
 use crate::{
     basic::{
@@ -11,6 +14,7 @@ use crate::{
     },
     hittable::HitRecord,
     pdf::PDF,
+    sampler::Sampler,
 };
 
 pub struct ScatterRecord {
@@ -23,15 +27,70 @@ pub struct ScatterRecord {
 
 pub trait Material: Send + Sync {
     #[allow(unused_variables)]
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut Option<ScatterRecord>) -> bool {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut Option<ScatterRecord>,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
         false
     }
+    // bsdf times cosine towards an arbitrary direction, zero for specular materials
+    #[allow(unused_variables)]
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        Vec3(0.0, 0.0, 0.0)
+    }
     #[allow(unused_variables)]
     fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
         0.0
     }
+    // participating media scatter inside a volume and have no surface normal
+    fn is_medium(&self) -> bool {
+        false
+    }
     #[allow(unused_variables)]
     fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
         Vec3(0.0, 0.0, 0.0)
     }
+    // the surface color guiding the denoiser, white for the colorless ones
+    #[allow(unused_variables)]
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        Vec3(1.0, 1.0, 1.0)
+    }
+    // set by `TaggedMaterial`, for the AOVs
+    fn material_id(&self) -> u32 {
+        0
+    }
+}
+
+// shared materials, e.g. the named ones of a scene file
+impl Material for Arc<dyn Material> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut Option<ScatterRecord>,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.as_ref().scatter(r_in, rec, srec, sampler)
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.as_ref().eval(r_in, rec, scattered)
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.as_ref().scattering_pdf(r_in, rec, scattered)
+    }
+    fn is_medium(&self) -> bool {
+        self.as_ref().is_medium()
+    }
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.as_ref().emitted(r_in, rec, u, v, p)
+    }
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.as_ref().albedo(rec)
+    }
+    fn material_id(&self) -> u32 {
+        self.as_ref().material_id()
+    }
 }
@@ -0,0 +1,47 @@
+use crate::{
+    basic::{
+        ray::Ray,
+        vec3::{Color, Point3},
+    },
+    hittable::HitRecord,
+    sampler::Sampler,
+};
+
+use super::{Material, ScatterRecord};
+
+// `mat` with a material ID for the AOVs
+#[derive(Clone)]
+pub struct TaggedMaterial<TM: Material> {
+    pub mat: TM,
+    pub id: u32,
+}
+
+impl<TM: Material> Material for TaggedMaterial<TM> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut Option<ScatterRecord>,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.mat.scatter(r_in, rec, srec, sampler)
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.mat.eval(r_in, rec, scattered)
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.mat.scattering_pdf(r_in, rec, scattered)
+    }
+    fn is_medium(&self) -> bool {
+        self.mat.is_medium()
+    }
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.mat.emitted(r_in, rec, u, v, p)
+    }
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.mat.albedo(rec)
+    }
+    fn material_id(&self) -> u32 {
+        self.id
+    }
+}
@@ -11,6 +11,7 @@ use crate::{
     bvh::BvhNode,
     hittable::{hittable_list::HittableList, objects::triangle::Triangle, Hittable},
     material::lambertian::Lambertian,
+    status_bar::show_note,
     texture::{image_texture::ImageTexture, obj_texture::ObjTexture},
 };
 
@@ -28,7 +29,7 @@ pub struct LoadOption<'a> {
 }
 
 #[allow(dead_code)]
-pub fn my_loader(id: u32, paras: LoadOption) -> Box<dyn Hittable> {
+pub fn my_loader(paras: LoadOption) -> Box<dyn Hittable> {
     let file_str = String::from(paras.path) + paras.file_name + ".obj";
     let obj = load_obj(file_str, &GPU_LOAD_OPTIONS);
     let (models, materials) = obj.unwrap();
@@ -69,13 +70,12 @@ pub fn my_loader(id: u32, paras: LoadOption) -> Box<dyn Hittable> {
 
         cnt += 1;
 
-        println!(
-            "Thread #{}, Name : {}, loading image {} / {}",
-            id,
+        show_note(&format!(
+            "Name : {}, loading image {} / {}",
             paras.file_name,
             cnt,
             models.len()
-        );
+        ));
 
         for id in md.mesh.indices.chunks(3) {
             let mut tri = Triangle::new_from_obj(
@@ -7,6 +7,7 @@ use crate::{
     bvh::BvhNode,
     hittable::{hittable_list::HittableList, objects::triangle::Triangle, Hittable},
     material::{lambertian::Lambertian, Material},
+    status_bar::show_note,
     texture::{image_texture::ImageTexture, obj_texture::ObjTexture, Texture},
 };
 
@@ -25,7 +26,6 @@ fn judge_light(mat: &ObjTexture, tex_uv: &[(f64, f64); 3]) -> bool {
 
 #[allow(dead_code)]
 pub fn treelight_loader<TM: Material + Clone + 'static>(
-    id: u32,
     paras: LoadOption,
     light_mat: TM,
 ) -> Box<dyn Hittable> {
@@ -70,13 +70,12 @@ pub fn treelight_loader<TM: Material + Clone + 'static>(
 
         cnt += 1;
 
-        println!(
-            "Thread #{}, Name : {}, loading image {} / {}",
-            id,
+        show_note(&format!(
+            "Name : {}, loading image {} / {}",
             paras.file_name,
             cnt,
             models.len()
-        );
+        ));
 
         for id in md.mesh.indices.chunks(3) {
             let tmp_tex = [
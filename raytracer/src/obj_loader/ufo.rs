@@ -7,6 +7,7 @@ use crate::{
     bvh::BvhNode,
     hittable::{hittable_list::HittableList, objects::triangle::Triangle, Hittable},
     material::{diffuse_light::DiffuseLight, lambertian::Lambertian},
+    status_bar::show_note,
     texture::{
         image_texture::ImageTexture, obj_texture::ObjTexture, solid_color_texture::SolidColor,
         Texture,
@@ -36,7 +37,7 @@ fn judge_light(mat: &ObjTexture, tex_uv: &[(f64, f64); 3], ret_col: &mut Vec3) -
     flag
 }
 
-pub fn ufo_loader(id: u32, paras: LoadOption) -> Box<dyn Hittable> {
+pub fn ufo_loader(paras: LoadOption) -> Box<dyn Hittable> {
     let file_str = String::from(paras.path) + paras.file_name + ".obj";
     let patrick = load_obj(file_str, &GPU_LOAD_OPTIONS);
     let (models, materials) = patrick.unwrap();
@@ -78,13 +79,12 @@ pub fn ufo_loader(id: u32, paras: LoadOption) -> Box<dyn Hittable> {
 
         cnt += 1;
 
-        println!(
-            "Thread #{}, Name : {}, loading image {} / {}",
-            id,
+        show_note(&format!(
+            "Name : {}, loading image {} / {}",
             paras.file_name,
             cnt,
             models.len()
-        );
+        ));
 
         for id in md.mesh.indices.chunks(3) {
             let tmp_tex = [
@@ -0,0 +1,165 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use image::{codecs::hdr::HdrEncoder, DynamicImage, ImageOutputFormat, Rgb};
+
+use crate::{basic::vec3::Color, config::config};
+
+// The image formats, picked by the extension of the output path. The HDR formats
+// get the linear radiance, the others the clamped and gamma-corrected colors.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Ppm,
+    Exr,
+    Hdr,
+    Pfm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "exr" => Some(ImageFormat::Exr),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+    pub fn is_hdr(self) -> bool {
+        matches!(self, ImageFormat::Exr | ImageFormat::Hdr | ImageFormat::Pfm)
+    }
+}
+
+// The size of the written images: the --crop window, unless it is pasted into the
+// full image, whose pixels outside it are black since they have no samples.
+pub fn size() -> (u32, u32) {
+    match (config().crop, config().crop_canvas) {
+        (Some((_, _, width, height)), false) => (width, height),
+        _ => (config().image_width, config().image_height),
+    }
+}
+
+// cuts the pixels of the full image, top row first, to `size()`
+pub fn crop<T: Copy>(pixels: &[T]) -> Vec<T> {
+    match (config().crop, config().crop_canvas) {
+        (Some((x, y, width, height)), false) => pixels
+            .chunks(config().image_width as usize)
+            .skip(y as usize)
+            .take(height as usize)
+            .flat_map(|row| row[x as usize..(x + width) as usize].iter().copied())
+            .collect(),
+        _ => pixels.to_vec(),
+    }
+}
+
+pub fn crop_image(img: DynamicImage) -> DynamicImage {
+    match (config().crop, config().crop_canvas) {
+        (Some((x, y, width, height)), false) => img.crop_imm(x, y, width, height),
+        _ => img,
+    }
+}
+
+pub fn write_ldr(path: &str, format: ImageFormat, img: &DynamicImage) -> io::Result<()> {
+    let format = match format {
+        ImageFormat::Png => ImageOutputFormat::Png,
+        // binary P6
+        ImageFormat::Ppm => ImageOutputFormat::Pnm(image::pnm::PNMSubtype::Pixmap(
+            image::pnm::SampleEncoding::Binary,
+        )),
+        _ => ImageOutputFormat::Jpeg(config().jpeg_quality),
+    };
+    let mut output_file = BufWriter::new(File::create(path)?);
+    img.write_to(&mut output_file, format)
+        .map_err(io::Error::other)?;
+    output_file.flush()
+}
+
+// `radiance` has the rows from the top of the image down, cropped
+pub fn write_hdr(path: &str, format: ImageFormat, radiance: &[Color]) -> io::Result<()> {
+    let (width, height) = size();
+    let (width, height) = (width as usize, height as usize);
+    match format {
+        ImageFormat::Exr => exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let color = radiance[y * width + x];
+            (color.0 as f32, color.1 as f32, color.2 as f32)
+        })
+        .map_err(io::Error::other),
+        ImageFormat::Hdr => {
+            let pixels: Vec<Rgb<f32>> = radiance
+                .iter()
+                .map(|color| Rgb([color.0 as f32, color.1 as f32, color.2 as f32]))
+                .collect();
+            let mut output_file = BufWriter::new(File::create(path)?);
+            HdrEncoder::new(&mut output_file)
+                .encode(&pixels, width, height)
+                .map_err(io::Error::other)?;
+            output_file.flush()
+        }
+        _ => {
+            // PFM: little-endian (negative scale) and the bottom row first
+            let mut output_file = BufWriter::new(File::create(path)?);
+            write!(output_file, "PF\n{} {}\n-1.0\n", width, height)?;
+            for row in radiance.chunks(width).rev() {
+                for color in row {
+                    for value in [color.0, color.1, color.2] {
+                        output_file.write_all(&(value as f32).to_le_bytes())?;
+                    }
+                }
+            }
+            output_file.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryInto, env, fs};
+
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn formats_by_extension() {
+        assert!(ImageFormat::from_path("a/b.JPEG") == Some(ImageFormat::Jpeg));
+        assert!(ImageFormat::from_path("b.png") == Some(ImageFormat::Png));
+        assert!(ImageFormat::from_path("out.v2.exr") == Some(ImageFormat::Exr));
+        assert!(ImageFormat::from_path("b.pfm") == Some(ImageFormat::Pfm));
+        assert!(ImageFormat::from_path("b.tiff").is_none());
+        assert!(ImageFormat::from_path("pfm").is_none());
+        assert!(ImageFormat::Hdr.is_hdr() && !ImageFormat::Ppm.is_hdr());
+    }
+
+    #[test]
+    fn pfm_keeps_the_radiance() {
+        config::load_for_tests();
+        let (width, height) = size();
+        let radiance: Vec<Color> = (0..width * height)
+            .map(|i| Color::new(i as f64, 0.5, -(i as f64) / 8.0))
+            .collect();
+        let path = env::temp_dir().join(format!("raytracer-{}-pfm.pfm", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        write_hdr(&path, ImageFormat::Pfm, &radiance).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        let header = format!("PF\n{} {}\n-1.0\n", width, height);
+        assert!(bytes.starts_with(header.as_bytes()));
+        let values: Vec<f32> = bytes[header.len()..]
+            .chunks(4)
+            .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        assert_eq!(values.len(), radiance.len() * 3);
+        // the bottom row first
+        let rows = radiance.chunks(width as usize).rev().flatten();
+        for (pixel, color) in values.chunks(3).zip(rows) {
+            assert_eq!(pixel, [color.0 as f32, color.1 as f32, color.2 as f32]);
+        }
+    }
+}
@@ -1,8 +1,11 @@
 use std::f64::consts::PI;
 
-use crate::basic::{
-    onb::Onb,
-    vec3::{dot, random_cosine_direction, Vec3},
+use crate::{
+    basic::{
+        onb::Onb,
+        vec3::{dot, random_cosine_direction, Vec3},
+    },
+    sampler::Sampler,
 };
 
 use super::PDF;
@@ -25,7 +28,7 @@ impl PDF for CosPDF {
         let cosine = dot(&direction.unit_vec(), &self.uvw.w());
         (cosine / PI).max(0.0)
     }
-    fn generate(&self) -> Vec3 {
-        self.uvw.local_by_vec3(random_cosine_direction())
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.uvw.local_by_vec3(random_cosine_direction(sampler))
     }
 }
@@ -1,10 +1,16 @@
-use rand::{thread_rng, Rng};
-
-use crate::basic::vec3::{Point3, Vec3};
+use crate::{
+    basic::vec3::{Point3, Vec3},
+    sampler::Sampler,
+};
 
 pub trait Lightable: Sync + Send {
     fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64;
-    fn random(&self, o: &Vec3) -> Vec3;
+    // the vector from `o` to a random point on the light
+    fn random(&self, o: &Vec3, sampler: &mut dyn Sampler) -> Vec3;
+    fn on_surface(&self, p: &Point3) -> bool;
+    // a uniformly distributed point on the light and the surface normal there
+    fn random_point(&self, sampler: &mut dyn Sampler) -> (Point3, Vec3);
+    fn area(&self) -> f64;
 }
 
 #[derive(Default)]
@@ -17,12 +23,49 @@ impl LightableList {
     pub fn add(&mut self, object: Box<dyn Lightable>) {
         self.lights.push(object);
     }
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+    // pdf of light sampling a direction `v` whose shadow ray from `o` ends at `p`,
+    // lights hidden behind `p` could not have been seen from `o` and don't count
+    pub fn visible_pdf_value(&self, o: &Point3, v: &Vec3, p: &Point3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.lights.len() as f64;
+        let mut sum = 0.0;
+        for obj in self.lights.iter() {
+            if obj.on_surface(p) {
+                sum += weight * obj.pdf_value(o, v);
+            }
+        }
+        sum
+    }
+    // area density of `random_point` producing `p`
+    pub fn point_pdf(&self, p: &Point3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.lights.len() as f64;
+        let mut sum = 0.0;
+        for obj in self.lights.iter() {
+            if obj.on_surface(p) {
+                sum += weight / obj.area();
+            }
+        }
+        sum
+    }
+    // uniform choice of a light
+    fn random_light(&self, sampler: &mut dyn Sampler) -> &dyn Lightable {
+        let index = (sampler.get_1d() * self.lights.len() as f64) as usize;
+        &*self.lights[index.min(self.lights.len() - 1)]
+    }
 }
 
 impl Lightable for LightableList {
     fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
         if self.lights.is_empty() {
-            return -1.0;
+            return 0.0;
         }
         let weight = 1.0 / self.lights.len() as f64;
         let mut sum = 0.0;
@@ -31,11 +74,22 @@ impl Lightable for LightableList {
         }
         sum
     }
-    fn random(&self, o: &crate::basic::vec3::Vec3) -> Vec3 {
+    fn random(&self, o: &crate::basic::vec3::Vec3, sampler: &mut dyn Sampler) -> Vec3 {
         if self.lights.is_empty() {
             return Vec3(0.0, 0.0, 0.0);
         }
-        let mut rng = thread_rng();
-        self.lights[rng.gen_range(0..self.lights.len())].random(o)
+        self.random_light(sampler).random(o, sampler)
+    }
+    fn on_surface(&self, p: &Point3) -> bool {
+        self.lights.iter().any(|obj| obj.on_surface(p))
+    }
+    fn random_point(&self, sampler: &mut dyn Sampler) -> (Point3, Vec3) {
+        if self.lights.is_empty() {
+            return (Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, 0.0));
+        }
+        self.random_light(sampler).random_point(sampler)
+    }
+    fn area(&self) -> f64 {
+        self.lights.iter().map(|obj| obj.area()).sum()
     }
 }
@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+// Multiple importance sampling weights (Veach, chapter 9)
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MisHeuristic {
+    Balance,
+    Power,
+}
+
+impl MisHeuristic {
+    // weight of one sample drawn by strategy f, while strategy g could also produce it
+    pub fn weight(self, f_pdf: f64, g_pdf: f64) -> f64 {
+        match self {
+            MisHeuristic::Balance => balance_heuristic(1, f_pdf, 1, g_pdf),
+            MisHeuristic::Power => power_heuristic(1, f_pdf, 1, g_pdf),
+        }
+    }
+}
+
+pub fn balance_heuristic(nf: u32, f_pdf: f64, ng: u32, g_pdf: f64) -> f64 {
+    let f = nf as f64 * f_pdf;
+    let g = ng as f64 * g_pdf;
+    if f + g <= 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+// beta = 2
+pub fn power_heuristic(nf: u32, f_pdf: f64, ng: u32, g_pdf: f64) -> f64 {
+    let f = nf as f64 * f_pdf;
+    let g = ng as f64 * g_pdf;
+    if f * f + g * g <= 0.0 {
+        return 0.0;
+    }
+    (f * f) / (f * f + g * g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_strategies_sum_to_one() {
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            for (f_pdf, g_pdf) in [(1.0, 1.0), (0.3, 2.5), (4.0, 1e-3), (0.0, 0.7)] {
+                let sum = heuristic.weight(f_pdf, g_pdf) + heuristic.weight(g_pdf, f_pdf);
+                assert!((sum - 1.0).abs() < 1e-12, "{} {}", f_pdf, g_pdf);
+            }
+        }
+    }
+
+    #[test]
+    fn sample_counts() {
+        assert_eq!(balance_heuristic(1, 1.0, 1, 1.0), 0.5);
+        assert_eq!(balance_heuristic(3, 1.0, 1, 1.0), 0.75);
+        assert_eq!(power_heuristic(1, 2.0, 1, 1.0), 0.8);
+        assert_eq!(power_heuristic(1, 1.0, 2, 1.0), 0.2);
+        assert_eq!(balance_heuristic(1, 0.0, 1, 0.0), 0.0);
+        assert_eq!(power_heuristic(1, 0.0, 1, 0.0), 0.0);
+    }
+}
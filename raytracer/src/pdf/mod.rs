@@ -1,11 +1,12 @@
 pub mod cos_pdf;
-pub mod hittable_pdf;
 pub mod lightable_list;
-pub mod mixture_pdf;
+pub mod mis;
+pub mod sphere_pdf;
 
-use crate::basic::vec3::Vec3;
+use crate::{basic::vec3::Vec3, sampler::Sampler};
 
+#[allow(clippy::upper_case_acronyms)]
 pub trait PDF {
     fn value(&self, direction: &Vec3) -> f64;
-    fn generate(&self) -> Vec3;
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3;
 }
@@ -0,0 +1,20 @@
+use std::f64::consts::PI;
+
+use crate::{
+    basic::vec3::{random_unit_vector, Vec3},
+    sampler::Sampler,
+};
+
+use super::PDF;
+
+// uniform over all directions, used by isotropic phase functions
+pub struct SpherePDF;
+
+impl PDF for SpherePDF {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        random_unit_vector(sampler)
+    }
+}
@@ -0,0 +1,133 @@
+use super::{hash, mix_bits, permutation_element, to_unit, Sampler};
+
+// how many dimensions get a prime base, later ones are independent
+const HALTON_DIMENSIONS: usize = 256;
+
+// The Halton sequence over the samples of a pixel, one prime base per dimension, with
+// the digits Owen scrambled per pixel and dimension (pbrt-v4 OwenScrambledRadicalInverse).
+pub struct HaltonSampler {
+    seed: u64,
+    primes: Vec<u64>,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        let mut primes = Vec::with_capacity(HALTON_DIMENSIONS);
+        let mut n = 2;
+        while primes.len() < HALTON_DIMENSIONS {
+            if primes.iter().all(|p| n % p != 0) {
+                primes.push(n);
+            }
+            n += 1;
+        }
+        HaltonSampler {
+            seed,
+            primes,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    // until the digits fall below the precision of a double
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let next = a / base;
+        let digit_hash = mix_bits(hash ^ reversed_digits) as u32;
+        let digit_value = permutation_element((a - next * base) as u32, base as u32, digit_hash);
+        // the large bases run out of integer bits first
+        match reversed_digits
+            .checked_mul(base)
+            .and_then(|r| r.checked_add(digit_value as u64))
+        {
+            Some(r) => reversed_digits = r,
+            None => break,
+        }
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (inv_base_m * reversed_digits as f64).min(1.0 - f64::EPSILON / 2.0)
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, px: u32, py: u32, index: u32) {
+        self.pixel = (px, py);
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn get_1d(&mut self) -> f64 {
+        let h = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+        ]);
+        let dimension = self.dimension as usize;
+        self.dimension += 1;
+        if dimension < self.primes.len() {
+            owen_scrambled_radical_inverse(self.primes[dimension], self.index as u64, h)
+        } else {
+            to_unit(hash(&[h, self.index as u64]))
+        }
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_in_unit_interval() {
+        let mut sampler = HaltonSampler::new(0);
+        for i in 0..64 {
+            sampler.start_pixel_sample(i % 5, i / 5, i);
+            // past the prime bases too
+            for _ in 0..HALTON_DIMENSIONS + 8 {
+                let u = sampler.get_1d();
+                assert!((0.0..1.0).contains(&u), "{}", u);
+            }
+        }
+    }
+
+    #[test]
+    fn dimensions_differ() {
+        let mut sampler = HaltonSampler::new(0);
+        let s: Vec<Vec<f64>> = (0..16)
+            .map(|i| {
+                sampler.start_pixel_sample(3, 7, i);
+                (0..4).map(|_| sampler.get_1d()).collect()
+            })
+            .collect();
+        for d in 1..4 {
+            assert!(s.iter().any(|v| v[d] != v[0]), "dimension {} repeats 0", d);
+        }
+    }
+
+    #[test]
+    fn scrambling_keeps_the_strata() {
+        // the first base^2 samples of a dimension fall one per stratum
+        let mut sampler = HaltonSampler::new(1);
+        for (d, &base) in [2_u32, 3].iter().enumerate() {
+            let n = base * base;
+            let mut strata: Vec<usize> = (0..n)
+                .map(|i| {
+                    sampler.start_pixel_sample(3, 7, i);
+                    let u = (0..=d).map(|_| sampler.get_1d()).last().unwrap();
+                    (u * n as f64) as usize
+                })
+                .collect();
+            strata.sort_unstable();
+            assert_eq!(strata, (0..n as usize).collect::<Vec<_>>(), "base {}", base);
+        }
+    }
+}
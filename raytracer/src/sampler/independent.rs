@@ -0,0 +1,31 @@
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use super::{hash, Sampler};
+
+// plain Monte Carlo, every dimension is a fresh uniform number from a stream of the
+// pixel sample
+pub struct IndependentSampler {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, px: u32, py: u32, index: u32) {
+        self.rng = StdRng::seed_from_u64(hash(&[self.seed, px as u64, py as u64, index as u64]));
+    }
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
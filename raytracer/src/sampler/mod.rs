@@ -0,0 +1,124 @@
+pub mod halton;
+pub mod independent;
+pub mod primary_sample;
+pub mod sobol;
+pub mod stratified;
+
+use std::cell::Cell;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::config::config;
+
+use self::{
+    halton::HaltonSampler, independent::IndependentSampler, sobol::SobolSampler,
+    stratified::StratifiedSampler,
+};
+
+// Every random decision of a render goes through a sampler. Each call takes the next
+// dimension of the current pixel sample, so the samplers that are not independent can
+// spread the samples of a pixel evenly in every dimension. What a sampler returns only
+// depends on its seed, the pixel, the sample index and the dimension, never on which
+// thread asks or what it rendered before, so a seed always gives the same image.
+pub trait Sampler {
+    // the sample `index` of pixel (px, py) begins
+    fn start_pixel_sample(&mut self, px: u32, py: u32, index: u32);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerType {
+    pub fn build(self, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerType::Stratified => {
+                Box::new(StratifiedSampler::new(seed, config().samples_per_pixel))
+            }
+            SamplerType::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+thread_local! {
+    static PATH_STREAM: Cell<u64> = const { Cell::new(0) };
+}
+
+// `Hittable::hit` has no sampler, so the media draw their free paths from a stream of
+// the thread instead, restarted from a seed of its own by every path that may use it
+pub fn start_path(seed: u64) {
+    PATH_STREAM.with(|state| state.set(seed));
+}
+
+pub fn path_1d() -> f64 {
+    PATH_STREAM.with(|state| {
+        let next = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state.set(next);
+        to_unit(mix_bits(next))
+    })
+}
+
+// MurmurHash3 finalizer
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, &v| {
+        mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15))
+    })
+}
+
+// uniform in [0, 1) from the high 53 bits
+pub fn to_unit(h: u64) -> f64 {
+    (h >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+// the element `i` of a random permutation of 0..l chosen by `p` (Kensler 2013)
+pub fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | (p >> 27));
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    i.wrapping_add(p) % l
+}
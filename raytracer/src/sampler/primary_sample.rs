@@ -0,0 +1,118 @@
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use super::Sampler;
+use crate::basic::PI;
+
+// Primary sample space for Metropolis light transport (Kelemen et al. 2002, pbrt-v3 16.4.4).
+// The sampler hands out the entries of its sample vector in order, so a path is a
+// deterministic function of that vector and mutating the vector mutates the path.
+// Entries are mutated lazily when first read.
+
+#[derive(Clone, Copy)]
+struct PrimarySample {
+    value: f64,
+    last_modification_iteration: u64,
+    // restored when a mutation is rejected
+    value_backup: f64,
+    modify_backup: u64,
+}
+
+pub struct PrimarySampler {
+    rng: StdRng,
+    sigma: f64,
+    large_step_probability: f64,
+    x: Vec<PrimarySample>,
+    current_iteration: u64,
+    large_step: bool,
+    last_large_step_iteration: u64,
+    sample_index: usize,
+}
+
+impl PrimarySampler {
+    pub fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        PrimarySampler {
+            rng: StdRng::seed_from_u64(seed),
+            sigma,
+            large_step_probability,
+            x: Vec::new(),
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            sample_index: 0,
+        }
+    }
+    pub fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.sample_index = 0;
+    }
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+    pub fn reject(&mut self) {
+        for xi in self.x.iter_mut() {
+            if xi.last_modification_iteration == self.current_iteration {
+                xi.value = xi.value_backup;
+                xi.last_modification_iteration = xi.modify_backup;
+            }
+        }
+        self.current_iteration -= 1;
+    }
+    fn next(&mut self) -> f64 {
+        let index = self.sample_index;
+        self.sample_index += 1;
+        // entries first read in a small step still have to start out uniform,
+        // rejection sampling loops would never leave a corner otherwise
+        while index >= self.x.len() {
+            let value = self.rng.gen();
+            self.x.push(PrimarySample {
+                value,
+                last_modification_iteration: self.current_iteration,
+                value_backup: value,
+                modify_backup: self.current_iteration,
+            });
+        }
+        self.ensure_ready(index);
+        self.x[index].value
+    }
+    // brings the entry up to the current iteration, replaying the mutations it missed
+    fn ensure_ready(&mut self, index: usize) {
+        let rng = &mut self.rng;
+        let xi = &mut self.x[index];
+
+        if xi.last_modification_iteration < self.last_large_step_iteration {
+            xi.value = rng.gen();
+            xi.last_modification_iteration = self.last_large_step_iteration;
+        }
+
+        xi.value_backup = xi.value;
+        xi.modify_backup = xi.last_modification_iteration;
+        if self.large_step {
+            xi.value = rng.gen();
+        } else {
+            // all the small steps since the last modification at once
+            let n_small = self.current_iteration - xi.last_modification_iteration;
+            let normal =
+                (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt() * (2.0 * PI * rng.gen::<f64>()).cos();
+            xi.value += normal * self.sigma * (n_small as f64).sqrt();
+            xi.value -= xi.value.floor();
+            if xi.value >= 1.0 {
+                xi.value = 0.0;
+            }
+        }
+        xi.last_modification_iteration = self.current_iteration;
+    }
+}
+
+// the pixel is chosen by the first two primary samples
+impl Sampler for PrimarySampler {
+    fn start_pixel_sample(&mut self, _px: u32, _py: u32, _index: u32) {}
+    fn get_1d(&mut self) -> f64 {
+        self.next()
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.next(), self.next())
+    }
+}
@@ -0,0 +1,139 @@
+use super::{hash, Sampler};
+
+// The first two Sobol dimensions with hash-based Owen scrambling (Burley 2020). Every
+// 1D or 2D request is padded with its own shuffle of the sample index, so each
+// dimension is stratified over the samples of a pixel without a table of directions.
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+    // shuffled index and scrambling seeds for the next dimension
+    fn next_seed(&mut self) -> u64 {
+        let h = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+        ]);
+        self.dimension += 1;
+        h
+    }
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// second Sobol dimension, from the primitive polynomial x + 1
+fn sobol_second(mut index: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+fn to_unit_u32(x: u32) -> f64 {
+    x as f64 / (1_u64 << 32) as f64
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, px: u32, py: u32, index: u32) {
+        self.pixel = (px, py);
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn get_1d(&mut self) -> f64 {
+        let h = self.next_seed();
+        let index = nested_uniform_scramble(self.index, h as u32);
+        to_unit_u32(nested_uniform_scramble(
+            index.reverse_bits(),
+            (h >> 32) as u32,
+        ))
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.next_seed();
+        let h2 = hash(&[h]);
+        let index = nested_uniform_scramble(self.index, h as u32);
+        (
+            to_unit_u32(nested_uniform_scramble(
+                index.reverse_bits(),
+                (h >> 32) as u32,
+            )),
+            to_unit_u32(nested_uniform_scramble(sobol_second(index), h2 as u32)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the first `dims` 1D values of each of `n` samples of a pixel
+    fn samples(sampler: &mut SobolSampler, n: u32, dims: usize) -> Vec<Vec<f64>> {
+        (0..n)
+            .map(|i| {
+                sampler.start_pixel_sample(3, 7, i);
+                (0..dims).map(|_| sampler.get_1d()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values_in_unit_interval() {
+        let mut sampler = SobolSampler::new(0);
+        for i in 0..256 {
+            sampler.start_pixel_sample(i % 5, i / 5, i);
+            for _ in 0..8 {
+                let u = sampler.get_1d();
+                let (a, b) = sampler.get_2d();
+                assert!((0.0..1.0).contains(&u), "{}", u);
+                assert!((0.0..1.0).contains(&a) && (0.0..1.0).contains(&b));
+            }
+        }
+    }
+
+    #[test]
+    fn dimensions_differ() {
+        let s = samples(&mut SobolSampler::new(0), 16, 4);
+        for d in 1..4 {
+            assert!(s.iter().any(|v| v[d] != v[0]), "dimension {} repeats 0", d);
+        }
+    }
+
+    #[test]
+    fn each_dimension_is_stratified() {
+        let s = samples(&mut SobolSampler::new(1), 16, 4);
+        for d in 0..4 {
+            let mut strata: Vec<usize> = s.iter().map(|v| (v[d] * 16.0) as usize).collect();
+            strata.sort_unstable();
+            assert_eq!(strata, (0..16).collect::<Vec<_>>(), "dimension {}", d);
+        }
+    }
+}
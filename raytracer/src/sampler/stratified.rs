@@ -0,0 +1,62 @@
+use super::{hash, permutation_element, to_unit, Sampler};
+
+// Jittered stratification of every dimension on its own: the samples of a pixel fall
+// into different strata, visited in a random order per pixel and dimension. Pairs use
+// a 2D grid of the largest square not above the sample count.
+pub struct StratifiedSampler {
+    seed: u64,
+    samples: u32,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples: u32) -> Self {
+        StratifiedSampler {
+            seed,
+            samples: samples.max(1),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+    // stratum of the current sample among `strata`, and jitters inside it
+    fn stratum(&mut self, strata: u32) -> (u32, (f64, f64)) {
+        let h = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            // samples beyond the strata start another round with a new permutation
+            (self.index / strata) as u64,
+        ]);
+        let stratum = permutation_element(self.index % strata, strata, h as u32);
+        let jitter = (
+            to_unit(hash(&[h, self.index as u64, 0])),
+            to_unit(hash(&[h, self.index as u64, 1])),
+        );
+        self.dimension += 1;
+        (stratum, jitter)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, px: u32, py: u32, index: u32) {
+        self.pixel = (px, py);
+        self.index = index;
+        self.dimension = 0;
+    }
+    fn get_1d(&mut self) -> f64 {
+        let (stratum, jitter) = self.stratum(self.samples);
+        (stratum as f64 + jitter.0) / self.samples as f64
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        let n = (self.samples as f64).sqrt() as u32;
+        let (stratum, jitter) = self.stratum(n * n);
+        (
+            ((stratum % n) as f64 + jitter.0) / n as f64,
+            ((stratum / n) as f64 + jitter.1) / n as f64,
+        )
+    }
+}
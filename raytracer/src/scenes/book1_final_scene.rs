@@ -1,17 +1,20 @@
 use rand::{prelude::StdRng, Rng, SeedableRng};
 
+use super::SceneOption;
 use crate::{
     basic::vec3::{Color, Point3, Vec3},
+    camera::Camera,
+    config::config,
     hittable::{
         hittable_list::HittableList,
         objects::{moving_sphere::MovingSphere, sphere::Sphere},
     },
     material::{dielectric::Dielectric, lambertian::Lambertian, metal::Metal},
+    pdf::lightable_list::LightableList,
     texture::{checker_texture::CheckerTexture, solid_color_texture::SolidColor},
 };
 
-#[allow(dead_code)]
-pub fn random_scene() -> HittableList {
+pub fn random_scene() -> SceneOption {
     let mut world = HittableList { objects: vec![] };
     let checker = CheckerTexture::<SolidColor, SolidColor>::new_by_color(
         Color::new(1.0, 0.5, 0.0),
@@ -113,5 +116,36 @@ pub fn random_scene() -> HittableList {
         mat: material3,
         is_ground: false,
     }));
-    world
+
+    SceneOption {
+        world,
+        lights: LightableList::default(),
+        cam: camera_generator(),
+        background: Color::new(0.70, 0.80, 1.00),
+        back_img: None,
+    }
+}
+
+fn camera_generator() -> Camera {
+    // Camera
+    let lookfrom = Vec3(13.0, 2.0, 3.0);
+    let lookat = Vec3(0.0, 0.0, 0.0);
+    let vfov: f64 = 20.0;
+    let aperture = 0.1;
+
+    // Camera
+    let vup: Vec3 = Vec3(0.0, 1.0, 0.0);
+    let dist_to_focus: f64 = 10.0;
+
+    Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        config().aspect_ratio(),
+        aperture,
+        dist_to_focus,
+        0.0,
+        1.0,
+    )
 }
@@ -1,23 +1,29 @@
 use rand::{prelude::StdRng, Rng, SeedableRng};
 
+use super::SceneOption;
 use crate::{
     basic::vec3::Vec3,
     bvh::BvhNode,
+    camera::Camera,
+    config::config,
     hittable::{
         hittable_list::HittableList,
-        instances::{constant_medium::ConstantMedium, rotate_y::RotateY, translate::Translate},
+        instances::{
+            constant_medium::ConstantMedium, flip_face::FlipFace, rotate_y::RotateY,
+            translate::Translate,
+        },
         objects::{aarect::XZRect, moving_sphere::MovingSphere, my_box::MyBox, sphere::Sphere},
     },
     material::{
         dielectric::Dielectric, diffuse_light::DiffuseLight, lambertian::Lambertian, metal::Metal,
     },
+    pdf::lightable_list::LightableList,
     texture::{
         image_texture::ImageTexture, noise_texture::NoiseTexture, solid_color_texture::SolidColor,
     },
 };
 
-#[allow(dead_code)]
-pub fn final_scene() -> HittableList {
+pub fn final_scene() -> SceneOption {
     let mut boxes1 = HittableList { objects: vec![] };
     let ground_material = Lambertian::<SolidColor>::new_by_solid_color(Vec3(0.48, 0.83, 0.53));
 
@@ -48,14 +54,18 @@ pub fn final_scene() -> HittableList {
 
     let light = DiffuseLight::new_by_color(Vec3(7.0, 7.0, 7.0));
 
-    objects.add(Box::new(XZRect {
+    let light = XZRect {
         x0: 123.0,
         x1: 423.0,
         z0: 147.0,
         z1: 412.0,
         k: 554.0,
         mat: light,
-    }));
+    };
+    // lights only emit on their front face, this one faces down into the scene
+    objects.add(Box::new(FlipFace { obj: light.clone() }));
+    let mut lights = LightableList::default();
+    lights.add(Box::new(light));
 
     let center1 = Vec3(400.0, 400.0, 200.0);
     let center2 = center1 + Vec3(30.0, 0.0, 0.0);
@@ -114,7 +124,7 @@ pub fn final_scene() -> HittableList {
     )));
 
     let earth_material = Lambertian::new_by_texture(ImageTexture::load_image_file(
-        "./raytracer/sources/yiyan.jpg",
+        "./raytracer/sources/Images/yiyan.jpg",
     ));
 
     let image_sphere = Box::new(Sphere {
@@ -155,5 +165,35 @@ pub fn final_scene() -> HittableList {
         offset: Vec3(-100.0, 270.0, 395.0),
     }));
 
-    objects
+    SceneOption {
+        world: objects,
+        lights,
+        cam: camera_generator(),
+        background: Vec3(0.0, 0.0, 0.0),
+        back_img: None,
+    }
+}
+
+fn camera_generator() -> Camera {
+    // Camera
+    let lookfrom = Vec3(478.0, 278.0, -600.0);
+    let lookat = Vec3(278.0, 278.0, 0.0);
+    let vfov: f64 = 40.0;
+    let aperture = 0.0;
+
+    // Camera
+    let vup: Vec3 = Vec3(0.0, 1.0, 0.0);
+    let dist_to_focus: f64 = 10.0;
+
+    Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        config().aspect_ratio(),
+        aperture,
+        dist_to_focus,
+        0.0,
+        1.0,
+    )
 }
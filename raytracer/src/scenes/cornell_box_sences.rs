@@ -1,5 +1,8 @@
+use super::SceneOption;
 use crate::{
     basic::vec3::Vec3,
+    camera::Camera,
+    config::config,
     hittable::{
         hittable_list::HittableList,
         instances::{flip_face::FlipFace, rotate_y::RotateY, translate::Translate},
@@ -12,11 +15,11 @@ use crate::{
     material::{
         dielectric::Dielectric, diffuse_light::DiffuseLight, lambertian::Lambertian, metal::Metal,
     },
+    pdf::lightable_list::LightableList,
     texture::solid_color_texture::SolidColor,
 };
 
-#[allow(dead_code)]
-pub fn cornell_box() -> (HittableList, HittableList) {
+pub fn cornell_box() -> SceneOption {
     let mut list = HittableList { objects: vec![] };
     let red = Lambertian::<SolidColor>::new_by_solid_color(Vec3(0.65, 0.05, 0.05));
     let white = Lambertian::<SolidColor>::new_by_solid_color(Vec3(0.73, 0.73, 0.73));
@@ -95,7 +98,7 @@ pub fn cornell_box() -> (HittableList, HittableList) {
         is_ground: false,
     }));
 
-    let mut lights = HittableList::default();
+    let mut lights = LightableList::default();
     lights.add(Box::new(XZRect {
         x0: 213.0,
         x1: 343.0,
@@ -111,5 +114,35 @@ pub fn cornell_box() -> (HittableList, HittableList) {
         is_ground: false,
     }));
 
-    (list, lights)
+    SceneOption {
+        world: list,
+        lights,
+        cam: camera_generator(),
+        background: Vec3(0.0, 0.0, 0.0),
+        back_img: None,
+    }
+}
+
+fn camera_generator() -> Camera {
+    // Camera
+    let lookfrom = Vec3(278.0, 278.0, -800.0);
+    let lookat = Vec3(278.0, 278.0, 0.0);
+    let vfov: f64 = 40.0;
+    let aperture = 0.0;
+
+    // Camera
+    let vup: Vec3 = Vec3(0.0, 1.0, 0.0);
+    let dist_to_focus: f64 = 10.0;
+
+    Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        config().aspect_ratio(),
+        aperture,
+        dist_to_focus,
+        0.0,
+        1.0,
+    )
 }
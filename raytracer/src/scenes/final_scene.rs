@@ -3,6 +3,7 @@ use rand::{prelude::StdRng, Rng, SeedableRng};
 use crate::{
     basic::vec3::{Color, Vec3},
     camera::Camera,
+    config::config,
     hittable::{
         hittable_list::HittableList,
         instances::{bump::BumpSurface, flip_face::FlipFace},
@@ -18,10 +19,9 @@ use crate::{
     },
     pdf::lightable_list::LightableList,
     texture::solid_color_texture::SolidColor,
-    ASPECT_RATIO,
 };
 
-use super::SceneOption;
+use super::{stars, SceneOption};
 
 fn light_get(f: f64) -> DiffuseLight<SolidColor> {
     DiffuseLight::new_by_color(Vec3(f, f, f))
@@ -85,7 +85,7 @@ fn basic_points_generator(world_list: &mut HittableList) {
     }));
 }
 
-fn lights_generator(_id: u32, world_list: &mut HittableList, light_list: &mut LightableList) {
+fn lights_generator(world_list: &mut HittableList, light_list: &mut LightableList) {
     let light_top = XZRect {
         x0: 200.0,
         x1: 400.0,
@@ -100,7 +100,7 @@ fn lights_generator(_id: u32, world_list: &mut HittableList, light_list: &mut Li
     light_list.add(Box::new(light_top));
 }
 
-fn forest_light_generator(id: u32, world_list: &mut HittableList) {
+fn forest_light_generator(world_list: &mut HittableList) {
     // treelight
     let _light_orange = DiffuseLight::new_by_color(Vec3(1.0, 69.0 / 255.0, 0.0) * 3.0);
     let _light_hotpink = DiffuseLight::new_by_color(Vec3(1.0, 105.0 / 255.0, 180.0 / 255.0) * 1.0);
@@ -124,7 +124,6 @@ fn forest_light_generator(id: u32, world_list: &mut HittableList) {
             r_z: 0.0,
         };
         world_list.add(treelight_loader(
-            id,
             _treelight_option,
             _light_orange.clone(),
             // match i {
@@ -136,7 +135,7 @@ fn forest_light_generator(id: u32, world_list: &mut HittableList) {
     }
 }
 
-fn moon_generator(id: u32, world_list: &mut HittableList) {
+fn moon_generator(world_list: &mut HittableList) {
     let _moon_option = LoadOption {
         path: "./raytracer/sources/Moon/",
         file_name: "moon",
@@ -149,7 +148,7 @@ fn moon_generator(id: u32, world_list: &mut HittableList) {
         r_z: 0.0,
     };
 
-    world_list.add(moon_loader(id, _moon_option));
+    world_list.add(moon_loader(_moon_option));
 }
 
 fn cuboid_generator(world_list: &mut HittableList, light_list: &mut LightableList) {
@@ -216,7 +215,7 @@ fn cuboid_generator(world_list: &mut HittableList, light_list: &mut LightableLis
     }
 }
 
-fn blackboard_generator(id: u32, world_list: &mut HittableList) {
+fn blackboard_generator(world_list: &mut HittableList) {
     let _blackboard_option = LoadOption {
         path: "./raytracer/sources/Blackboard/",
         file_name: "blackboard",
@@ -228,11 +227,11 @@ fn blackboard_generator(id: u32, world_list: &mut HittableList) {
         r_y: 180.0,
         r_z: 0.0,
     };
-    let blackboard = my_loader(id, _blackboard_option);
+    let blackboard = my_loader(_blackboard_option);
     world_list.add(blackboard);
 }
 
-fn city_platform_generator(id: u32, world_list: &mut HittableList, light_list: &mut LightableList) {
+fn city_platform_generator(world_list: &mut HittableList, light_list: &mut LightableList) {
     let _platform_option = LoadOption {
         path: "./raytracer/sources/Cityp_Platform/",
         file_name: "city_platform",
@@ -244,7 +243,7 @@ fn city_platform_generator(id: u32, world_list: &mut HittableList, light_list: &
         r_y: 270.0,
         r_z: 0.0,
     };
-    let platform = my_loader(id, _platform_option);
+    let platform = my_loader(_platform_option);
     world_list.add(platform);
 
     let _ufo_option = LoadOption {
@@ -258,7 +257,7 @@ fn city_platform_generator(id: u32, world_list: &mut HittableList, light_list: &
         r_y: 0.0,
         r_z: 0.0,
     };
-    let ufo = ufo_loader(id, _ufo_option);
+    let ufo = ufo_loader(_ufo_option);
     world_list.add(ufo);
 
     let _alien_option = LoadOption {
@@ -272,7 +271,7 @@ fn city_platform_generator(id: u32, world_list: &mut HittableList, light_list: &
         r_y: 150.0,
         r_z: 0.0,
     };
-    let alien = my_loader(id, _alien_option);
+    let alien = my_loader(_alien_option);
     world_list.add(alien);
     let light_top = XZRect {
         x0: 1000.0,
@@ -289,7 +288,7 @@ fn city_platform_generator(id: u32, world_list: &mut HittableList, light_list: &
     light_list.add(Box::new(light_top));
 }
 
-fn beach_generator(id: u32, world_list: &mut HittableList, light_list: &mut LightableList) {
+fn beach_generator(world_list: &mut HittableList, light_list: &mut LightableList) {
     let _beach_option = LoadOption {
         path: "./raytracer/sources/Beach/",
         file_name: "beach",
@@ -301,7 +300,7 @@ fn beach_generator(id: u32, world_list: &mut HittableList, light_list: &mut Ligh
         r_y: 0.0,
         r_z: 0.0,
     };
-    let beach = my_loader(id, _beach_option);
+    let beach = my_loader(_beach_option);
     world_list.add(beach);
 
     let _banana_option = LoadOption {
@@ -315,7 +314,7 @@ fn beach_generator(id: u32, world_list: &mut HittableList, light_list: &mut Ligh
         r_y: -60.0,
         r_z: 0.0,
     };
-    let banana0 = my_loader(id, _banana_option);
+    let banana0 = my_loader(_banana_option);
     world_list.add(banana0);
 
     let _babara_option = LoadOption {
@@ -329,7 +328,7 @@ fn beach_generator(id: u32, world_list: &mut HittableList, light_list: &mut Ligh
         r_y: 140.0,
         r_z: 0.0,
     };
-    world_list.add(my_loader(id, _babara_option));
+    world_list.add(my_loader(_babara_option));
 
     let _patrick_option = LoadOption {
         path: "./raytracer/sources/Patrick/",
@@ -342,7 +341,7 @@ fn beach_generator(id: u32, world_list: &mut HittableList, light_list: &mut Ligh
         r_y: 150.0,
         r_z: 0.0,
     };
-    world_list.add(my_loader(id, _patrick_option));
+    world_list.add(my_loader(_patrick_option));
 
     let light_front = XYRect {
         x0: 700.0,
@@ -356,7 +355,7 @@ fn beach_generator(id: u32, world_list: &mut HittableList, light_list: &mut Ligh
     light_list.add(Box::new(light_front));
 }
 
-fn pyramid_generator(id: u32, world_list: &mut HittableList) {
+fn pyramid_generator(world_list: &mut HittableList) {
     let _pyramid_option = LoadOption {
         path: "./raytracer/sources/Pyramid_Monsters/",
         file_name: "pyramid_monsters",
@@ -368,7 +367,7 @@ fn pyramid_generator(id: u32, world_list: &mut HittableList) {
         r_y: 180.0,
         r_z: 0.0,
     };
-    world_list.add(my_loader(id, _pyramid_option));
+    world_list.add(my_loader(_pyramid_option));
 
     let _beepee_option = LoadOption {
         path: "./raytracer/sources/Beepee/",
@@ -381,10 +380,10 @@ fn pyramid_generator(id: u32, world_list: &mut HittableList) {
         r_y: 200.0,
         r_z: 0.0,
     };
-    world_list.add(my_loader(id, _beepee_option));
+    world_list.add(my_loader(_beepee_option));
 }
 
-fn glass_and_frog_generator(id: u32, world_list: &mut HittableList) {
+fn glass_and_frog_generator(world_list: &mut HittableList) {
     let _glass_option = LoadOption {
         path: "./raytracer/sources/Glass/",
         file_name: "glass",
@@ -396,7 +395,7 @@ fn glass_and_frog_generator(id: u32, world_list: &mut HittableList) {
         r_y: 210.0,
         r_z: 0.0,
     };
-    world_list.add(my_loader(id, _glass_option));
+    world_list.add(my_loader(_glass_option));
 
     let _frog_option = LoadOption {
         path: "./raytracer/sources/Frog/",
@@ -409,10 +408,10 @@ fn glass_and_frog_generator(id: u32, world_list: &mut HittableList) {
         r_y: 150.0,
         r_z: 0.0,
     };
-    world_list.add(my_loader(id, _frog_option));
+    world_list.add(my_loader(_frog_option));
 }
 
-pub fn final_scene(id: u32) -> SceneOption {
+pub fn final_scene() -> SceneOption {
     let mut world_list = HittableList::default();
     let mut light_list = LightableList::default();
 
@@ -421,23 +420,23 @@ pub fn final_scene(id: u32) -> SceneOption {
 
     ground_generator(&mut world_list);
 
-    lights_generator(id, &mut world_list, &mut light_list);
+    lights_generator(&mut world_list, &mut light_list);
 
     cuboid_generator(&mut world_list, &mut light_list);
 
-    blackboard_generator(id, &mut world_list);
+    blackboard_generator(&mut world_list);
 
-    city_platform_generator(id, &mut world_list, &mut light_list);
+    city_platform_generator(&mut world_list, &mut light_list);
 
-    beach_generator(id, &mut world_list, &mut light_list);
+    beach_generator(&mut world_list, &mut light_list);
 
-    moon_generator(id, &mut world_list);
+    moon_generator(&mut world_list);
 
-    pyramid_generator(id, &mut world_list);
+    pyramid_generator(&mut world_list);
 
-    forest_light_generator(id, &mut world_list);
+    forest_light_generator(&mut world_list);
 
-    glass_and_frog_generator(id, &mut world_list);
+    glass_and_frog_generator(&mut world_list);
 
     let _green = Lambertian::<SolidColor>::new_by_solid_color(Vec3(0.0, 1.0, 0.0));
     let _alumium = Metal {
@@ -454,6 +453,7 @@ pub fn final_scene(id: u32) -> SceneOption {
         lights: light_list,
         cam: camera_generator(),
         background,
+        back_img: Some(stars()),
     }
 }
 
@@ -473,7 +473,7 @@ pub fn camera_generator() -> Camera {
         lookat,
         vup,
         vfov,
-        ASPECT_RATIO,
+        config().aspect_ratio(),
         aperture,
         dist_to_focus,
         0.0,
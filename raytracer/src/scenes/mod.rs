@@ -1,17 +1,53 @@
+use std::sync::Arc;
+
 use crate::{
-    basic::vec3::Vec3, camera::Camera, hittable::hittable_list::HittableList,
+    basic::{background::Background, vec3::Vec3},
+    camera::Camera,
+    hittable::hittable_list::HittableList,
     pdf::lightable_list::LightableList,
+    texture::image_texture::ImageTexture,
 };
 
+use self::scene_file::{is_scene_file, SceneFile};
+
 pub mod book1_final_scene;
 pub mod book2_final_scene;
 pub mod cornell_box_sences;
 pub mod final_scene;
 pub mod obj_test_scene;
+pub mod scene_file;
 
 pub struct SceneOption {
     pub world: HittableList,
     pub lights: LightableList,
     pub cam: Camera,
     pub background: Vec3,
+    // seen by the camera rays that miss everything, instead of `background`
+    pub back_img: Option<Background>,
+}
+
+pub fn stars() -> Background {
+    Background {
+        img: Arc::new(ImageTexture::load_image_file(
+            "./raytracer/sources/Images/background.jpg",
+        )),
+    }
+}
+
+// the scenes `--scene` can pick, built once and shared by all threads,
+// a .json or .toml path is read as a scene file
+pub fn scene_by_name(name: &str) -> Option<Box<dyn Fn() -> SceneOption>> {
+    if is_scene_file(name) {
+        let file = SceneFile::read(name);
+        return Some(Box::new(move || file.build()));
+    }
+    let scene: fn() -> SceneOption = match name {
+        "random_scene" => book1_final_scene::random_scene,
+        "book2_final_scene" => book2_final_scene::final_scene,
+        "cornell_box" => cornell_box_sences::cornell_box,
+        "obj_test_scene" => obj_test_scene::obj_test_scene,
+        "final_scene" => final_scene::final_scene,
+        _ => return None,
+    };
+    Some(Box::new(scene))
 }
@@ -1,7 +1,8 @@
-use super::SceneOption;
+use super::{stars, SceneOption};
 use crate::{
     basic::vec3::Vec3,
     camera::Camera,
+    config::config,
     hittable::{
         hittable_list::HittableList,
         instances::flip_face::FlipFace,
@@ -11,7 +12,6 @@ use crate::{
     obj_loader::{my_loader, LoadOption},
     pdf::lightable_list::LightableList,
     texture::solid_color_texture::SolidColor,
-    ASPECT_RATIO,
 };
 
 #[allow(dead_code)]
@@ -20,7 +20,7 @@ fn light_get(f: f64) -> DiffuseLight<SolidColor> {
 }
 
 #[allow(dead_code)]
-pub fn obj_test_scene(id: u32) -> SceneOption {
+pub fn obj_test_scene() -> SceneOption {
     let mut list = HittableList { objects: vec![] };
     let red = Lambertian::<SolidColor>::new_by_solid_color(Vec3(0.65, 0.05, 0.05));
     let white = Lambertian::<SolidColor>::new_by_solid_color(Vec3(0.73, 0.73, 0.73));
@@ -117,8 +117,8 @@ pub fn obj_test_scene(id: u32) -> SceneOption {
     };
 
     //list.add(my_loader(_patrick_option));
-    //list.add(my_loader(id, _babara_option));
-    list.add(my_loader(id, _patrick_option));
+    //list.add(my_loader(_babara_option));
+    list.add(my_loader(_patrick_option));
 
     let mut lights = LightableList::default();
     lights.add(Box::new(light_top));
@@ -139,6 +139,7 @@ pub fn obj_test_scene(id: u32) -> SceneOption {
         lights,
         cam: camera_generator(),
         background: Vec3(0.0, 0.0, 0.0),
+        back_img: Some(stars()),
     }
 }
 
@@ -158,7 +159,7 @@ fn camera_generator() -> Camera {
         lookat,
         vup,
         vfov,
-        ASPECT_RATIO,
+        config().aspect_ratio(),
         aperture,
         dist_to_focus,
         0.0,
@@ -0,0 +1,648 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use serde::Deserialize;
+
+use super::SceneOption;
+use crate::{
+    basic::{background::Background, vec3::Vec3},
+    bvh::BvhNode,
+    camera::Camera,
+    config::config,
+    hittable::{
+        hittable_list::HittableList,
+        instances::{
+            bump::BumpSurface, constant_medium::ConstantMedium, flip_face::FlipFace,
+            rotate_y::RotateY, tagged::Tagged, translate::Translate,
+        },
+        objects::{
+            aarect::{XYRect, XZRect, YZRect},
+            moving_sphere::MovingSphere,
+            my_box::MyBox,
+            sphere::Sphere,
+        },
+        Hittable,
+    },
+    material::{
+        dielectric::Dielectric, diffuse_light::DiffuseLight, lambertian::Lambertian, metal::Metal,
+        tagged::TaggedMaterial, Material,
+    },
+    obj_loader::{
+        moon::moon_loader, my_loader, treelight::treelight_loader, ufo::ufo_loader, LoadOption,
+    },
+    pdf::lightable_list::{Lightable, LightableList},
+    texture::{
+        checker_texture::CheckerTexture, image_texture::ImageTexture, noise_texture::NoiseTexture,
+        solid_color_texture::SolidColor, Texture,
+    },
+};
+
+// A scene written as JSON or TOML instead of Rust, see raytracer/scenes/ for examples.
+// Everything the Rust scenes build has a `type` here: textures, materials, objects,
+// instances wrapping an `object`, and the `lights` that are sampled directly, which only
+// need their shape as the lights of `SceneOption` do. Textures and materials can be
+// named once and used by name, or written inline where they are used. Colors and points
+// are [x, y, z].
+// For the AOVs the top-level objects get the object IDs 1, 2, ... in order, unless a
+// `tagged` object inside sets its own. The named materials get the material IDs 1, 2,
+// ... in the order of their names, the inline ones the IDs after them.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    camera: CameraDesc,
+    #[serde(default)]
+    background: Vec3,
+    // seen by the camera rays that miss everything, instead of `background`
+    background_image: Option<String>,
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: Vec3,
+    lookat: Vec3,
+    #[serde(default = "default_vup")]
+    vup: Vec3,
+    vfov: f64,
+    #[serde(default)]
+    aperture: f64,
+    #[serde(default = "default_focus_dist")]
+    focus_dist: f64,
+    #[serde(default)]
+    time0: f64,
+    #[serde(default = "default_time1")]
+    time1: f64,
+}
+
+fn default_vup() -> Vec3 {
+    Vec3(0.0, 1.0, 0.0)
+}
+fn default_focus_dist() -> f64 {
+    10.0
+}
+fn default_time1() -> f64 {
+    1.0
+}
+fn default_one() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: Vec3 },
+    Checker { odd: TextureRef, even: TextureRef },
+    Image { file: String },
+    Noise { scale: f64 },
+}
+
+// a color, the name of a texture or a texture written in place
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color(Vec3),
+    Name(String),
+    Inline(Box<TextureDesc>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    Metal {
+        albedo: Vec3,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        ir: f64,
+    },
+    DiffuseLight {
+        emit: TextureRef,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaterialRef {
+    Name(String),
+    Inline(MaterialDesc),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum ObjLoader {
+    #[default]
+    Default,
+    Moon,
+    Ufo,
+    TreeLight,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        material: MaterialRef,
+        // textures wrap around a huge ground sphere instead of stretching over it
+        #[serde(default)]
+        ground: bool,
+    },
+    MovingSphere {
+        center0: Vec3,
+        center1: Vec3,
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+        radius: f64,
+        material: MaterialRef,
+    },
+    XyRect {
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+        material: MaterialRef,
+    },
+    XzRect {
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: MaterialRef,
+    },
+    YzRect {
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: MaterialRef,
+    },
+    #[serde(rename = "box")]
+    MyBox {
+        p0: Vec3,
+        p1: Vec3,
+        material: MaterialRef,
+    },
+    // a model loaded from `path` + `file_name` + ".obj", as the LoadOption of obj_loader
+    Obj {
+        #[serde(default)]
+        loader: ObjLoader,
+        path: String,
+        file_name: String,
+        #[serde(default = "default_one")]
+        zoom_rate: f64,
+        #[serde(default)]
+        zoom_orig: Vec3,
+        #[serde(default)]
+        offset: Vec3,
+        #[serde(default)]
+        set_flag: bool,
+        #[serde(default)]
+        r_x: f64,
+        #[serde(default)]
+        r_y: f64,
+        #[serde(default)]
+        r_z: f64,
+        // for the lamps of the tree_light loader
+        light_material: Option<MaterialRef>,
+    },
+    List {
+        objects: Vec<ObjectDesc>,
+    },
+    Bvh {
+        objects: Vec<ObjectDesc>,
+    },
+    Translate {
+        object: Box<ObjectDesc>,
+        offset: Vec3,
+    },
+    RotateY {
+        object: Box<ObjectDesc>,
+        angle: f64,
+    },
+    FlipFace {
+        object: Box<ObjectDesc>,
+    },
+    Tagged {
+        object: Box<ObjectDesc>,
+        id: u32,
+    },
+    ConstantMedium {
+        boundary: Box<ObjectDesc>,
+        density: f64,
+        color: Vec3,
+    },
+    Bump {
+        object: Box<ObjectDesc>,
+        normal_map: String,
+        rate: u32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+    },
+    XyRect {
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+    },
+    XzRect {
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+    },
+    YzRect {
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+    },
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
+
+pub fn is_scene_file(name: &str) -> bool {
+    name.ends_with(".json") || name.ends_with(".toml")
+}
+
+impl SceneFile {
+    // parses the file by its extension, exits on errors
+    pub fn read(path: &str) -> SceneFile {
+        let text = fs::read_to_string(path)
+            .unwrap_or_else(|err| fail(format!("Cannot read scene file {}: {}", path, err)));
+        let parsed = if path.ends_with(".toml") {
+            toml::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        };
+        parsed.unwrap_or_else(|err| fail(format!("Invalid scene file {}: {}", path, err)))
+    }
+
+    pub fn build(&self) -> SceneOption {
+        let mut builder = Builder {
+            file: self,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            resolving: vec![],
+            next_material_id: self.materials.len() as u32 + 1,
+        };
+        let mut world = HittableList::default();
+        for (index, desc) in self.objects.iter().enumerate() {
+            world.add(Box::new(Tagged {
+                obj: builder.object(desc),
+                id: index as u32 + 1,
+            }));
+        }
+        let mut lights = LightableList::default();
+        for desc in self.lights.iter() {
+            lights.add(light(desc));
+        }
+
+        let cam = &self.camera;
+        SceneOption {
+            world,
+            lights,
+            cam: Camera::new(
+                cam.lookfrom,
+                cam.lookat,
+                cam.vup,
+                cam.vfov,
+                config().aspect_ratio(),
+                cam.aperture,
+                cam.focus_dist,
+                cam.time0,
+                cam.time1,
+            ),
+            background: self.background,
+            back_img: self.background_image.as_ref().map(|file| Background {
+                img: Arc::new(ImageTexture::load_image_file(file)),
+            }),
+        }
+    }
+}
+
+// builds the named textures and materials once, on their first use
+struct Builder<'a> {
+    file: &'a SceneFile,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    // the names being built, to catch a texture made of itself
+    resolving: Vec<String>,
+    next_material_id: u32,
+}
+
+impl Builder<'_> {
+    fn texture_ref(&mut self, r: &TextureRef) -> Arc<dyn Texture> {
+        match r {
+            TextureRef::Color(c) => Arc::new(SolidColor::new_from_color(*c)),
+            TextureRef::Inline(desc) => self.texture(desc),
+            TextureRef::Name(name) => {
+                if let Some(tex) = self.textures.get(name) {
+                    return tex.clone();
+                }
+                if self.resolving.contains(name) {
+                    fail(format!("Texture {} refers to itself", name));
+                }
+                let file = self.file;
+                let desc = file
+                    .textures
+                    .get(name)
+                    .unwrap_or_else(|| fail(format!("Unknown texture {}", name)));
+                self.resolving.push(name.clone());
+                let tex = self.texture(desc);
+                self.resolving.pop();
+                self.textures.insert(name.clone(), tex.clone());
+                tex
+            }
+        }
+    }
+
+    fn texture(&mut self, desc: &TextureDesc) -> Arc<dyn Texture> {
+        match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new_from_color(*color)),
+            TextureDesc::Checker { odd, even } => Arc::new(CheckerTexture::new(
+                self.texture_ref(odd),
+                self.texture_ref(even),
+            )),
+            TextureDesc::Image { file } => Arc::new(ImageTexture::load_image_file(file)),
+            TextureDesc::Noise { scale } => Arc::new(NoiseTexture::new_by_sc(*scale)),
+        }
+    }
+
+    fn material_ref(&mut self, r: &MaterialRef) -> Arc<dyn Material> {
+        match r {
+            MaterialRef::Inline(desc) => {
+                let id = self.next_material_id;
+                self.next_material_id += 1;
+                let mat = self.material(desc);
+                Arc::new(TaggedMaterial { mat, id })
+            }
+            MaterialRef::Name(name) => {
+                if let Some(mat) = self.materials.get(name) {
+                    return mat.clone();
+                }
+                let file = self.file;
+                let desc = file
+                    .materials
+                    .get(name)
+                    .unwrap_or_else(|| fail(format!("Unknown material {}", name)));
+                let mut names: Vec<&String> = file.materials.keys().collect();
+                names.sort();
+                let id = names.iter().position(|other| *other == name).unwrap() as u32 + 1;
+                let mat: Arc<dyn Material> = Arc::new(TaggedMaterial {
+                    mat: self.material(desc),
+                    id,
+                });
+                self.materials.insert(name.clone(), mat.clone());
+                mat
+            }
+        }
+    }
+
+    fn material(&mut self, desc: &MaterialDesc) -> Arc<dyn Material> {
+        match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                Arc::new(Lambertian::new_by_texture(self.texture_ref(albedo)))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal {
+                albedo: *albedo,
+                fuzz: *fuzz,
+            }),
+            MaterialDesc::Dielectric { ir } => Arc::new(Dielectric { ir: *ir }),
+            MaterialDesc::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new_by_texture(self.texture_ref(emit)))
+            }
+        }
+    }
+
+    fn object(&mut self, desc: &ObjectDesc) -> Box<dyn Hittable> {
+        match desc {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+                ground,
+            } => Box::new(Sphere {
+                center: *center,
+                radius: *radius,
+                mat: self.material_ref(material),
+                is_ground: *ground,
+            }),
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => Box::new(MovingSphere {
+                center0: *center0,
+                center1: *center1,
+                time0: *time0,
+                time1: *time1,
+                radius: *radius,
+                mat: self.material_ref(material),
+            }),
+            ObjectDesc::XyRect {
+                x0,
+                x1,
+                y0,
+                y1,
+                k,
+                material,
+            } => Box::new(XYRect {
+                x0: *x0,
+                x1: *x1,
+                y0: *y0,
+                y1: *y1,
+                k: *k,
+                mat: self.material_ref(material),
+            }),
+            ObjectDesc::XzRect {
+                x0,
+                x1,
+                z0,
+                z1,
+                k,
+                material,
+            } => Box::new(XZRect {
+                x0: *x0,
+                x1: *x1,
+                z0: *z0,
+                z1: *z1,
+                k: *k,
+                mat: self.material_ref(material),
+            }),
+            ObjectDesc::YzRect {
+                y0,
+                y1,
+                z0,
+                z1,
+                k,
+                material,
+            } => Box::new(YZRect {
+                y0: *y0,
+                y1: *y1,
+                z0: *z0,
+                z1: *z1,
+                k: *k,
+                mat: self.material_ref(material),
+            }),
+            ObjectDesc::MyBox { p0, p1, material } => {
+                Box::new(MyBox::new(*p0, *p1, self.material_ref(material)))
+            }
+            ObjectDesc::Obj {
+                loader,
+                path,
+                file_name,
+                zoom_rate,
+                zoom_orig,
+                offset,
+                set_flag,
+                r_x,
+                r_y,
+                r_z,
+                light_material,
+            } => {
+                let option = LoadOption {
+                    path,
+                    file_name,
+                    zoom_rate: *zoom_rate,
+                    zoom_orig: *zoom_orig,
+                    offset: *offset,
+                    set_flag: *set_flag,
+                    r_x: *r_x,
+                    r_y: *r_y,
+                    r_z: *r_z,
+                };
+                match loader {
+                    ObjLoader::Default => my_loader(option),
+                    ObjLoader::Moon => moon_loader(option),
+                    ObjLoader::Ufo => ufo_loader(option),
+                    ObjLoader::TreeLight => {
+                        let light_mat = match light_material {
+                            Some(mat) => self.material_ref(mat),
+                            None => fail(format!("{} needs a light_material", file_name)),
+                        };
+                        treelight_loader(option, light_mat)
+                    }
+                }
+            }
+            ObjectDesc::List { objects } => Box::new(self.list(objects)),
+            ObjectDesc::Bvh { objects } => {
+                let list = self.list(objects);
+                Box::new(BvhNode::new_from_list(
+                    list,
+                    self.file.camera.time0,
+                    self.file.camera.time1,
+                ))
+            }
+            ObjectDesc::Translate { object, offset } => Box::new(Translate {
+                obj: self.object(object),
+                offset: *offset,
+            }),
+            ObjectDesc::RotateY { object, angle } => {
+                Box::new(RotateY::new_by_angle(self.object(object), *angle))
+            }
+            ObjectDesc::FlipFace { object } => Box::new(FlipFace {
+                obj: self.object(object),
+            }),
+            ObjectDesc::Tagged { object, id } => Box::new(Tagged {
+                obj: self.object(object),
+                id: *id,
+            }),
+            ObjectDesc::ConstantMedium {
+                boundary,
+                density,
+                color,
+            } => Box::new(ConstantMedium::new_by_color(
+                self.object(boundary),
+                *density,
+                *color,
+            )),
+            ObjectDesc::Bump {
+                object,
+                normal_map,
+                rate,
+            } => Box::new(BumpSurface::new_from_obj_and_normal_map(
+                self.object(object),
+                normal_map,
+                *rate,
+            )),
+        }
+    }
+
+    fn list(&mut self, objects: &[ObjectDesc]) -> HittableList {
+        let mut list = HittableList::default();
+        for desc in objects.iter() {
+            list.add(self.object(desc));
+        }
+        list
+    }
+}
+
+fn light(desc: &LightDesc) -> Box<dyn Lightable> {
+    // the material doesn't matter
+    let mat = Dielectric { ir: 1.0 };
+    match *desc {
+        LightDesc::Sphere { center, radius } => Box::new(Sphere {
+            center,
+            radius,
+            mat,
+            is_ground: false,
+        }),
+        LightDesc::XyRect { x0, x1, y0, y1, k } => Box::new(XYRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            mat,
+        }),
+        LightDesc::XzRect { x0, x1, z0, z1, k } => Box::new(XZRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            mat,
+        }),
+        LightDesc::YzRect { y0, y1, z0, z1, k } => Box::new(YZRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            mat,
+        }),
+    }
+}
@@ -0,0 +1,216 @@
+use std::{cell::Cell, fs, io, sync::Mutex, time::Duration};
+
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    basic::vec3::Color,
+    config::config,
+    status_bar::{event, show_output, ProgressMode},
+};
+
+// Render statistics with --stats: every thread counts into its own counters, which
+// are added to the totals after every tile or render thread. The report is printed at
+// the end and written next to the image as `<output>_stats.json`, distributed workers
+// write theirs next to their parts, which are added up on merging.
+
+#[derive(Clone, Copy)]
+pub enum Counter {
+    PrimaryRays,
+    // bounces, shadow rays, light subpaths and photons
+    SecondaryRays,
+    BvhNodeTests,
+    PrimitiveTests,
+    // camera paths and the surfaces they hit
+    Paths,
+    PathVertices,
+    // paths still going at `max_depth`
+    MaxDepthPaths,
+    NanSamples,
+    InfSamples,
+}
+
+const COUNTERS: usize = 9;
+
+thread_local! {
+    static LOCAL: [Cell<u64>; COUNTERS] = const { [const { Cell::new(0) }; COUNTERS] };
+}
+
+static TOTALS: Mutex<[u64; COUNTERS]> = Mutex::new([0; COUNTERS]);
+
+// the render time and the merged parts
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+pub fn add(counter: Counter, n: u64) {
+    if !config().stats {
+        return;
+    }
+    LOCAL.with(|local| {
+        let cell = &local[counter as usize];
+        cell.set(cell.get() + n);
+    });
+}
+
+pub fn count(counter: Counter) {
+    add(counter, 1);
+}
+
+// adds the counters of this thread to the totals
+pub fn flush() {
+    let mut totals = TOTALS.lock().unwrap();
+    LOCAL.with(|local| {
+        for (total, cell) in totals.iter_mut().zip(local.iter()) {
+            *total += cell.take();
+        }
+    });
+}
+
+// counts NaN and infinite samples, NaN components are dropped
+pub fn check_sample(color: &mut Color) {
+    if (0..3).any(|t| color[t].is_nan()) {
+        count(Counter::NanSamples);
+    } else if (0..3).any(|t| color[t].is_infinite()) {
+        count(Counter::InfSamples);
+    }
+    for t in 0..3 {
+        if color[t].is_nan() {
+            color[t] = 0.0;
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Report {
+    pub seconds: f64,
+    // summed over the render threads, of all workers
+    pub thread_seconds: f64,
+    pub primary_rays: u64,
+    pub secondary_rays: u64,
+    pub rays_per_second_per_thread: f64,
+    pub bvh_node_tests: u64,
+    pub primitive_tests: u64,
+    pub paths: u64,
+    pub path_vertices: u64,
+    pub average_path_length: f64,
+    pub max_depth_paths: u64,
+    pub max_depth_share: f64,
+    pub nan_samples: u64,
+    pub inf_samples: u64,
+}
+
+impl Report {
+    fn add(&mut self, other: &Report) {
+        self.seconds = self.seconds.max(other.seconds);
+        self.thread_seconds += other.thread_seconds;
+        self.primary_rays += other.primary_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.bvh_node_tests += other.bvh_node_tests;
+        self.primitive_tests += other.primitive_tests;
+        self.paths += other.paths;
+        self.path_vertices += other.path_vertices;
+        self.max_depth_paths += other.max_depth_paths;
+        self.nan_samples += other.nan_samples;
+        self.inf_samples += other.inf_samples;
+    }
+
+    fn with_rates(mut self) -> Self {
+        let rays = (self.primary_rays + self.secondary_rays) as f64;
+        let paths = self.paths.max(1) as f64;
+        self.rays_per_second_per_thread = rays / self.thread_seconds.max(1e-9);
+        self.average_path_length = self.path_vertices as f64 / paths;
+        self.max_depth_share = self.max_depth_paths as f64 / paths;
+        self
+    }
+}
+
+// called when this process has rendered, with the time it took
+pub fn rendered(elapsed: Duration) {
+    let mut report = REPORT.lock().unwrap();
+    let report = report.get_or_insert_with(Report::default);
+    report.seconds = report.seconds.max(elapsed.as_secs_f64());
+    report.thread_seconds += elapsed.as_secs_f64() * config().thread_num as f64;
+}
+
+fn current() -> Report {
+    let totals = *TOTALS.lock().unwrap();
+    let mut report = Report {
+        primary_rays: totals[Counter::PrimaryRays as usize],
+        secondary_rays: totals[Counter::SecondaryRays as usize],
+        bvh_node_tests: totals[Counter::BvhNodeTests as usize],
+        primitive_tests: totals[Counter::PrimitiveTests as usize],
+        paths: totals[Counter::Paths as usize],
+        path_vertices: totals[Counter::PathVertices as usize],
+        max_depth_paths: totals[Counter::MaxDepthPaths as usize],
+        nan_samples: totals[Counter::NanSamples as usize],
+        inf_samples: totals[Counter::InfSamples as usize],
+        ..Report::default()
+    };
+    if let Some(other) = &*REPORT.lock().unwrap() {
+        report.add(other);
+    }
+    report.with_rates()
+}
+
+pub fn write(path: &str) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(&current()).unwrap();
+    fs::write(path, json + "\n")
+}
+
+// adds the stats a worker wrote next to its part, if it did
+pub fn merge(path: &str) {
+    let part = match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str::<Report>(&text),
+        Err(_) => return,
+    };
+    match part {
+        Ok(part) => REPORT
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Report::default)
+            .add(&part),
+        Err(err) => eprintln!("{} {}: {}", style("Invalid stats").red(), path, err),
+    }
+}
+
+pub fn report(image_path: &str) {
+    if !config().stats {
+        return;
+    }
+    let report = current();
+    let stem = image_path
+        .rsplit_once('.')
+        .map_or(image_path, |(stem, _)| stem);
+    let path = format!("{}_stats.json", stem);
+    if config().progress == ProgressMode::Json {
+        event("stats", serde_json::to_value(&report).unwrap());
+        show_output("stats", &path, write(&path));
+        return;
+    }
+
+    let line = |name: &str, value: String| {
+        println!("         {:<27}{}", name, style(value).yellow());
+    };
+    println!("📊 {}", style("Render statistics").green());
+    line("Render time:", format!("{:.2} s", report.seconds));
+    line("Primary rays:", report.primary_rays.to_string());
+    line("Secondary rays:", report.secondary_rays.to_string());
+    line(
+        "Rays/s per thread:",
+        format!("{:.0}", report.rays_per_second_per_thread),
+    );
+    line("BVH node tests:", report.bvh_node_tests.to_string());
+    line("Primitive tests:", report.primitive_tests.to_string());
+    line(
+        "Average path length:",
+        format!("{:.3}", report.average_path_length),
+    );
+    line(
+        "Paths ended by max depth:",
+        format!("{:.4}%", report.max_depth_share * 100.0),
+    );
+    line(
+        "NaN / inf samples:",
+        format!("{} / {}", report.nan_samples, report.inf_samples),
+    );
+    show_output("stats", &path, write(&path));
+}
@@ -1,33 +1,344 @@
+use std::{
+    fmt::Display,
+    process::exit,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Instant,
+};
+
+use clap::ValueEnum;
 use console::style;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::config;
+
+// How the render reports its progress: the progress bars need a terminal, batch jobs
+// get plain lines every tenth of the work or one JSON event per line, e.g.
+//
+//     {"done":18,"elapsed":1.25,"event":"tile_done","pass":0,"tile":17,"total":40}
+//
+// The events are `started`, `tile_done`, `pass_done`, `iteration_done` of the
+// integrators rendering whole iterations, `interrupted`, `checkpoint_saved`, `resumed`,
+// `time_limit`, `worker_done`, `part_merged`, `output` for every file written, `stats`
+// and `finished` with the timings. Nothing else goes to stdout then, errors go to stderr.
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProgressMode {
+    // bars if stdout is a terminal, plain lines otherwise
+    Auto,
+    Bar,
+    Plain,
+    Json,
+}
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+fn elapsed() -> f64 {
+    START.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
+fn elapsed_precise() -> String {
+    let seconds = elapsed() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
 
-use crate::{IMAGE_HEIGHT, IMAGE_WIDTH, MAX_DEPTH, SAMPLES_PER_PIXEL, THREAD_NUM};
+// one JSON line with the event name and the time since the start
+pub fn event(name: &str, fields: Value) {
+    let mut line = json!({ "event": name, "elapsed": elapsed() });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    println!("{}", line);
+}
 
 pub fn show_image_information(path: &str) {
-    print!("{}[2J", 27 as char); // clear screen
-    print!("{esc}[2J{esc}[1;1H", esc = 27 as char); // set cursor at 1,1
+    START.get_or_init(Instant::now);
+    match config().progress {
+        ProgressMode::Json => {
+            event(
+                "started",
+                json!({
+                    "output": path,
+                    "scene": config().scene,
+                    "width": config().image_width,
+                    "height": config().image_height,
+                    "spp": config().samples_per_pixel,
+                    "max_depth": config().max_depth,
+                    "threads": config().thread_num,
+                }),
+            );
+            return;
+        }
+        ProgressMode::Bar => {
+            print!("{}[2J", 27 as char); // clear screen
+            print!("{esc}[2J{esc}[1;1H", esc = 27 as char); // set cursor at 1,1
+        }
+        _ => {}
+    }
     println!(
         "         Image name:                {}",
         style(path.to_string()).yellow()
     );
+    println!(
+        "         Scene:                     {}",
+        style(config().scene.clone()).yellow()
+    );
     println!(
         "         Image size:                {}",
-        style(IMAGE_WIDTH.to_string() + &"x".to_string() + &IMAGE_HEIGHT.to_string()).yellow()
+        style(format!(
+            "{}x{}",
+            config().image_width,
+            config().image_height
+        ))
+        .yellow()
     );
     println!(
         "         Sample number per pixel:   {}",
-        style(SAMPLES_PER_PIXEL.to_string()).yellow()
+        style(config().samples_per_pixel.to_string()).yellow()
     );
     println!(
         "         Reflection max depth:      {}",
-        style(MAX_DEPTH.to_string()).yellow()
+        style(config().max_depth.to_string()).yellow()
     );
 }
 
 pub fn show_thread_information() {
+    if config().progress == ProgressMode::Json {
+        return;
+    }
     println!(
         "🚀 {} {} {}",
         style("Rendering with").green(),
-        style(THREAD_NUM.to_string()).yellow(),
+        style(config().thread_num.to_string()).yellow(),
         style("Threads...").green(),
     );
 }
+
+// the render and the whole run in seconds
+pub fn show_finished(render_seconds: f64) {
+    match config().progress {
+        ProgressMode::Json => event(
+            "finished",
+            json!({ "render_seconds": render_seconds, "total_seconds": elapsed() }),
+        ),
+        _ => println!(
+            "🏁 {} {:.2} s, {:.2} s in all",
+            style("Rendered in").green(),
+            render_seconds,
+            elapsed()
+        ),
+    }
+}
+
+// a line for people only, the JSON events have no place for it
+pub fn show_note(line: &str) {
+    if config().progress != ProgressMode::Json {
+        println!("{}", line);
+    }
+}
+
+// the file of the image, the parts, the AOVs, the heatmap or the stats at `path` once
+// it is `written`, failing to write it ends the process
+pub fn show_output<E: Display>(kind: &str, path: &str, written: Result<(), E>) {
+    if let Err(err) = written {
+        if config().progress == ProgressMode::Json {
+            let message = err.to_string();
+            event(
+                "error",
+                json!({ "kind": kind, "path": path, "message": message }),
+            );
+        }
+        eprintln!(
+            "{} \"{}\": {}",
+            style(format!("Writing the {} fails", kind)).red(),
+            path,
+            err
+        );
+        exit(2);
+    }
+    match config().progress {
+        ProgressMode::Json => event("output", json!({ "kind": kind, "path": path })),
+        _ => println!("Output {} as \"{}\"", kind, style(path).yellow()),
+    }
+}
+
+pub fn show_checkpoint_saved(path: &str) {
+    match config().progress {
+        ProgressMode::Json => event("checkpoint_saved", json!({ "path": path })),
+        _ => println!(
+            "Saved checkpoint \"{}\", continue with --resume",
+            style(path).yellow()
+        ),
+    }
+}
+
+pub fn show_resumed(pass: u32, path: &str) {
+    match config().progress {
+        ProgressMode::Json => event("resumed", json!({ "pass": pass, "path": path })),
+        _ => println!("Resuming pass {} from \"{}\"", pass, style(path).yellow()),
+    }
+}
+
+// the samples per pixel on average and at least and most
+pub fn show_time_limit_reached(mean: f64, min: u32, max: u32) {
+    match config().progress {
+        ProgressMode::Json => event(
+            "time_limit",
+            json!({ "mean_spp": mean, "min_spp": min, "max_spp": max }),
+        ),
+        _ => println!(
+            "⏱  {} {} {}",
+            style("Time limit reached with").green(),
+            style(format!("{:.1}", mean)).yellow(),
+            style(format!("samples per pixel ({} to {})", min, max)).green()
+        ),
+    }
+}
+
+pub fn show_worker_done(index: u32) {
+    match config().progress {
+        ProgressMode::Json => event("worker_done", json!({ "worker": index })),
+        _ => println!("Worker {} finished", index),
+    }
+}
+
+pub fn show_part_merged(path: &str) {
+    match config().progress {
+        ProgressMode::Json => event("part_merged", json!({ "path": path })),
+        _ => println!("Merged part \"{}\"", style(path).yellow()),
+    }
+}
+
+// The progress of the tiles of a pass, or of the iterations of one render thread
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    // the render thread, None for the tiles
+    thread: Option<u32>,
+    pass: AtomicU64,
+    // locked while reporting, so the lines come in order
+    done: Mutex<u64>,
+    total: u64,
+}
+
+impl Progress {
+    fn new(bar: Option<ProgressBar>, thread: Option<u32>, total: u64) -> Self {
+        Progress {
+            bar,
+            thread,
+            pass: AtomicU64::new(0),
+            done: Mutex::new(0),
+            total,
+        }
+    }
+
+    pub fn tiles(total: u64) -> Self {
+        let bar = (config().progress == ProgressMode::Bar).then(|| {
+            let bar = ProgressBar::new(total);
+            bar.set_style(ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] [{pos}/{len}] {msg} ({eta})")
+                .progress_chars("#>-"));
+            bar
+        });
+        Progress::new(bar, None, total)
+    }
+
+    pub fn thread(bars: &MultiProgress, id: u32, total: u64) -> Self {
+        let bar = (config().progress == ProgressMode::Bar).then(|| {
+            let bar = bars.add(ProgressBar::new(total));
+            bar.set_style(ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] [{pos}/{len}] ({eta})")
+                .progress_chars("#>-"));
+            bar
+        });
+        Progress::new(bar, Some(id), total)
+    }
+
+    // a pass starts with `done` tiles written back already
+    pub fn start_pass(&self, pass: u32, done: u64) {
+        self.pass.store(pass as u64, Ordering::Relaxed);
+        *self.done.lock().unwrap() = done;
+        if let Some(bar) = &self.bar {
+            bar.set_position(done);
+            bar.set_message(format!("pass {}", pass));
+        }
+    }
+
+    // tile `index` or the next iteration is done
+    pub fn inc(&self, index: usize) {
+        let mut count = self.done.lock().unwrap();
+        *count += 1;
+        let done = *count;
+        let pass = self.pass.load(Ordering::Relaxed);
+        match (config().progress, self.thread) {
+            (ProgressMode::Bar, _) => self.bar.as_ref().unwrap().inc(1),
+            (ProgressMode::Json, None) => event(
+                "tile_done",
+                json!({ "pass": pass, "tile": index, "done": done, "total": self.total }),
+            ),
+            (ProgressMode::Json, Some(thread)) => event(
+                "iteration_done",
+                json!({ "thread": thread, "iteration": index, "done": done, "total": self.total }),
+            ),
+            _ => {
+                // every tenth
+                let step = |done: u64| done * 10 / self.total.max(1);
+                if step(done) > step(done - 1) {
+                    let what = match self.thread {
+                        Some(thread) => format!("thread {}", thread),
+                        None => format!("pass {}", pass),
+                    };
+                    println!(
+                        "[{}] {}: {}/{} ({}%)",
+                        elapsed_precise(),
+                        what,
+                        done,
+                        self.total,
+                        done * 100 / self.total.max(1)
+                    );
+                }
+            }
+        }
+    }
+
+    // the pass is done, `batch` samples per pixel come next, 0 if none
+    pub fn finish_pass(&self, pass: u32, batch: u32) {
+        match config().progress {
+            ProgressMode::Json => event("pass_done", json!({ "pass": pass, "next_batch": batch })),
+            ProgressMode::Plain => println!(
+                "[{}] pass {} done, {}",
+                elapsed_precise(),
+                pass,
+                if batch > 0 {
+                    format!("{} more samples per pixel next", batch)
+                } else {
+                    "render done".to_string()
+                }
+            ),
+            _ => {}
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_with_message("Finished.");
+        }
+    }
+
+    pub fn abandon(&self) {
+        match (&self.bar, config().progress) {
+            (Some(bar), _) => bar.abandon_with_message("Interrupted."),
+            (None, ProgressMode::Json) => event("interrupted", json!({})),
+            _ => println!("[{}] interrupted", elapsed_precise()),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use crate::basic::vec3::{Color, Point3};
 
 pub mod checker_texture;
@@ -9,3 +11,9 @@ pub mod solid_color_texture;
 pub trait Texture: Sync + Send {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
 }
+
+impl Texture for Arc<dyn Texture> {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.as_ref().value(u, v, p)
+    }
+}
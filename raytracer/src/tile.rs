@@ -0,0 +1,175 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{config::config, TILE_SIZE};
+
+// A square block of pixels rendered as one job, `x1` and `y1` are exclusive and
+// `y` counts from the bottom row up. The tiles at the right and top borders are cut
+// to the image, or to the --crop window, which is all that is rendered then.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+// The order the tiles are handed out in, idle threads then steal from the front.
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TileOrder {
+    // outwards from the center of the image, which usually matters most
+    Spiral,
+    // along a Hilbert curve, neighbouring tiles share their geometry in the caches
+    Hilbert,
+}
+
+// the pixels to render, the --crop window or the whole image
+pub fn window() -> Tile {
+    crop_window(config().crop, config().image_width, config().image_height)
+}
+
+fn crop_window(crop: Option<(u32, u32, u32, u32)>, image_width: u32, image_height: u32) -> Tile {
+    match crop {
+        // the crop is given from the top row down
+        Some((x, y, width, height)) => Tile {
+            x0: x,
+            y0: image_height - y - height,
+            x1: x + width,
+            y1: image_height - y,
+        },
+        None => Tile {
+            x0: 0,
+            y0: 0,
+            x1: image_width,
+            y1: image_height,
+        },
+    }
+}
+
+impl TileOrder {
+    pub fn tiles(self) -> Vec<Tile> {
+        let window = window();
+        let nx = (window.x1 - window.x0).div_ceil(TILE_SIZE);
+        let ny = (window.y1 - window.y0).div_ceil(TILE_SIZE);
+        let cells = match self {
+            TileOrder::Spiral => spiral(nx, ny),
+            TileOrder::Hilbert => hilbert(nx, ny),
+        };
+        cells
+            .into_iter()
+            .map(|(tx, ty)| Tile {
+                x0: window.x0 + tx * TILE_SIZE,
+                y0: window.y0 + ty * TILE_SIZE,
+                x1: (window.x0 + (tx + 1) * TILE_SIZE).min(window.x1),
+                y1: (window.y0 + (ty + 1) * TILE_SIZE).min(window.y1),
+            })
+            .collect()
+    }
+}
+
+// walks a square spiral around the center cell, keeping the cells inside the grid
+fn spiral(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let mut cells = Vec::with_capacity((nx * ny) as usize);
+    let (mut x, mut y) = (((nx - 1) / 2) as i64, ((ny - 1) / 2) as i64);
+    let (mut dx, mut dy) = (1_i64, 0_i64);
+    let mut run = 1;
+    while cells.len() < (nx * ny) as usize {
+        for _turn in 0..2 {
+            for _step in 0..run {
+                if x >= 0 && y >= 0 && x < nx as i64 && y < ny as i64 {
+                    cells.push((x as u32, y as u32));
+                }
+                x += dx;
+                y += dy;
+            }
+            let turned = (-dy, dx);
+            dx = turned.0;
+            dy = turned.1;
+        }
+        run += 1;
+    }
+    cells
+}
+
+// sorts the cells by their distance along a Hilbert curve over the enclosing power of two
+fn hilbert(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let n = nx.max(ny).next_power_of_two();
+    let mut cells: Vec<(u32, u32)> = (0..ny)
+        .flat_map(|ty| (0..nx).map(move |tx| (tx, ty)))
+        .collect();
+    cells.sort_by_key(|&(tx, ty)| {
+        let (mut x, mut y) = (tx, ty);
+        let mut d = 0_u64;
+        let mut s = n / 2;
+        while s > 0 {
+            let rx = (x & s > 0) as u32;
+            let ry = (y & s > 0) as u32;
+            d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+            // rotate the quadrant so the curve stays continuous
+            if ry == 0 {
+                if rx == 1 {
+                    x = n - 1 - x;
+                    y = n - 1 - y;
+                }
+                std::mem::swap(&mut x, &mut y);
+            }
+            s /= 2;
+        }
+        d
+    });
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_cover_every_cell_once() {
+        for (nx, ny) in [(1, 1), (1, 5), (4, 4), (5, 3), (7, 12), (80, 50)] {
+            for cells in [spiral(nx, ny), hilbert(nx, ny)] {
+                let mut sorted = cells.clone();
+                sorted.sort_unstable();
+                sorted.dedup();
+                assert_eq!(sorted.len(), cells.len());
+                assert_eq!(cells.len(), (nx * ny) as usize);
+                assert!(cells.iter().all(|&(x, y)| x < nx && y < ny));
+            }
+            // the spiral starts in the center
+            assert_eq!(spiral(nx, ny)[0], ((nx - 1) / 2, (ny - 1) / 2));
+        }
+    }
+
+    #[test]
+    fn hilbert_steps_to_a_neighbour() {
+        let cells = hilbert(8, 8);
+        for pair in cells.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn crop_window_counts_rows_from_the_bottom() {
+        let tile = |x0, y0, x1, y1| Tile { x0, y0, x1, y1 };
+        assert_eq!(crop_window(None, 64, 48), tile(0, 0, 64, 48));
+        assert_eq!(
+            crop_window(Some((0, 0, 64, 48)), 64, 48),
+            tile(0, 0, 64, 48)
+        );
+        // the top left corner of the image is its last row
+        assert_eq!(
+            crop_window(Some((0, 0, 10, 5)), 64, 48),
+            tile(0, 43, 10, 48)
+        );
+        assert_eq!(
+            crop_window(Some((54, 43, 10, 5)), 64, 48),
+            tile(54, 0, 64, 5)
+        );
+        assert_eq!(
+            crop_window(Some((5, 10, 20, 8)), 64, 48),
+            tile(5, 30, 25, 38)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{basic::vec3::Color, config::config};
+
+// The display transform of the LDR formats: exposure, a tone curve to [0, 1] and
+// the sRGB transfer function. The HDR formats get the radiance as it is.
+
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMap {
+    // clamps at 1
+    None,
+    // L / (1 + L) on the luminance
+    Reinhard,
+    // Reinhard reaching 1 at the `white` luminance
+    ReinhardExtended,
+    // John Hable's Uncharted 2 filmic curve
+    Hable,
+    // Stephen Hill's fit of the ACES reference and output transforms
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(self, c: Color) -> Color {
+        match self {
+            ToneMap::None => c,
+            ToneMap::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+            ToneMap::ReinhardExtended => {
+                let white = config().white_point;
+                scale_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMap::Hable => {
+                // the exposure bias and linear white point of the original
+                const WHITE: f64 = 11.2;
+                let white_scale = 1.0 / hable(WHITE);
+                let c = c * 2.0;
+                Color::new(hable(c.0), hable(c.1), hable(c.2)) * white_scale
+            }
+            ToneMap::Aces => aces_fitted(c),
+        }
+    }
+}
+
+fn scale_luminance(c: Color, curve: impl Fn(f64) -> f64) -> Color {
+    let l = c.luminance();
+    if l <= 0.0 {
+        return c;
+    }
+    c * (curve(l) / l)
+}
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn aces_fitted(c: Color) -> Color {
+    // sRGB to the ACES RRT input space, with the saturation of the RRT
+    let input = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT output back to sRGB
+    let output = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let mul = |m: [[f64; 3]; 3], c: Color| {
+        Color::new(
+            m[0][0] * c.0 + m[0][1] * c.1 + m[0][2] * c.2,
+            m[1][0] * c.0 + m[1][1] * c.1 + m[1][2] * c.2,
+            m[2][0] * c.0 + m[2][1] * c.1 + m[2][2] * c.2,
+        )
+    };
+    let rrt_odt =
+        |x: f64| (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.432951) + 0.238081);
+    let c = mul(input, c);
+    mul(output, Color::new(rrt_odt(c.0), rrt_odt(c.1), rrt_odt(c.2)))
+}
+
+// linear to the sRGB curve, for values in [0, 1]
+pub fn srgb_encode(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// averaged radiance to display values in [0, 1]
+pub fn display(c: Color) -> Color {
+    let c = config().tone_map.apply(c * 2_f64.powf(config().exposure));
+    let encode = |x: f64| srgb_encode(x.clamp(0.0, 1.0));
+    Color::new(encode(c.0), encode(c.1), encode(c.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    // the inverse of `srgb_encode`
+    fn srgb_decode(x: f64) -> f64 {
+        if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        config::load_for_tests();
+        for tone_map in ToneMap::value_variants() {
+            let mut last = -1.0;
+            for i in 0..=2000 {
+                let l = i as f64 / 100.0;
+                let mapped = tone_map.apply(Color::new(l, l, l));
+                assert!(mapped.1 >= last, "{} falls at {}", mapped.1, l);
+                last = mapped.1;
+            }
+        }
+    }
+
+    #[test]
+    fn reinhard_extended_reaches_white() {
+        let white = config::load_for_tests().white_point;
+        let mapped = ToneMap::ReinhardExtended.apply(Color::new(white, white, white));
+        assert!((mapped.0 - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=1000 {
+            let x = i as f64 / 1000.0;
+            assert!((srgb_decode(srgb_encode(x)) - x).abs() < 1e-9, "{}", x);
+        }
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,65 @@
+// shared by the test binaries, each of which uses only some of it
+#![allow(dead_code)]
+
+use std::{convert::TryInto, env, fs, path::PathBuf, process::Command};
+
+// renders a small Cornell box with the extra arguments into image.pfm and returns every
+// file written, by name, every test in a directory of its own since they run in parallel
+pub fn render_files(name: &str, args: &[&str]) -> Vec<(String, Vec<u8>)> {
+    let dir = env::temp_dir().join(format!("raytracer-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    let scene: PathBuf = [env!("CARGO_MANIFEST_DIR"), "scenes", "cornell_box.json"]
+        .iter()
+        .collect();
+    let status = Command::new(env!("CARGO_BIN_EXE_raytracer"))
+        .arg("--scene")
+        .arg(&scene)
+        .args(["--width", "48", "--height", "30", "--progress", "plain"])
+        .arg("--output")
+        .arg(dir.join("image.pfm"))
+        .args(args)
+        .output()
+        .expect("Running the renderer fails");
+    assert!(
+        status.status.success(),
+        "{}",
+        String::from_utf8_lossy(&status.stderr)
+    );
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(&path).unwrap())
+        })
+        .collect();
+    files.sort();
+    fs::remove_dir_all(&dir).ok();
+    files
+}
+
+// the bytes of the rendered image.pfm
+pub fn render(name: &str, args: &[&str]) -> Vec<u8> {
+    render_files(name, args)
+        .into_iter()
+        .find(|(file, _)| file == "image.pfm")
+        .expect("The image is not written")
+        .1
+}
+
+// (width, height) and all channels of a .pfm
+pub fn pixels(image: &[u8]) -> ((u32, u32), Vec<f32>) {
+    // the header is three lines: "PF", the size and the scale
+    let mut lines = image.splitn(4, |&byte| byte == b'\n');
+    assert_eq!(lines.next(), Some(&b"PF"[..]));
+    let size = String::from_utf8_lossy(lines.next().unwrap()).into_owned();
+    let mut size = size.split(' ').map(|n| n.parse().unwrap());
+    let size = (size.next().unwrap(), size.next().unwrap());
+    let values = lines
+        .nth(1)
+        .unwrap()
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    (size, values)
+}
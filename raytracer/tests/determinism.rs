@@ -0,0 +1,129 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{pixels, render, render_files};
+
+#[test]
+fn threads_render_the_same_image() {
+    let one = render("one_thread", &["--threads", "1", "--spp", "4"]);
+    let four = render("four_threads", &["--threads", "4", "--spp", "4"]);
+    assert!(one == four, "1 and 4 threads render different images");
+}
+
+#[test]
+fn threads_render_the_same_adaptive_image() {
+    let one = render(
+        "adaptive_one_thread",
+        &["--threads", "1", "--spp", "80", "--adaptive"],
+    );
+    let four = render(
+        "adaptive_four_threads",
+        &["--threads", "4", "--spp", "80", "--adaptive"],
+    );
+    assert!(
+        one == four,
+        "1 and 4 threads render different adaptive images"
+    );
+}
+
+#[test]
+fn seed_changes_the_image() {
+    let seed0 = render("seed0", &["--threads", "2", "--spp", "4", "--seed", "0"]);
+    let seed1 = render("seed1", &["--threads", "2", "--spp", "4", "--seed", "1"]);
+    assert_eq!(seed0.len(), seed1.len());
+    assert!(seed0 != seed1, "the seed does not change the image");
+}
+
+#[test]
+fn progressive_passes_render_the_same_image() {
+    let single = render("single_pass", &["--threads", "2", "--spp", "8"]);
+    let progressive = render(
+        "progressive",
+        &["--threads", "2", "--spp", "8", "--progressive", "3"],
+    );
+    assert!(
+        single == progressive,
+        "--progressive changes the final image"
+    );
+}
+
+#[test]
+fn denoised_images_stay_finite() {
+    let cases: [(&str, &[&str]); 3] = [
+        ("denoise_one_sample", &["--spp", "1"]),
+        ("denoise_crop", &["--spp", "4", "--crop", "10,5,20,12"]),
+        (
+            "denoise_crop_canvas",
+            &["--spp", "4", "--crop", "10,5,20,12", "--crop-canvas"],
+        ),
+    ];
+    for (name, args) in cases {
+        let args: Vec<_> = ["--threads", "2", "--denoise"]
+            .iter()
+            .chain(args)
+            .copied()
+            .collect();
+        let (size, values) = pixels(&render(name, &args));
+        let expected = if name == "denoise_crop" {
+            (20, 12)
+        } else {
+            (48, 30)
+        };
+        assert_eq!(size, expected, "{}", name);
+        assert!(values.iter().all(|value| value.is_finite()), "{}", name);
+    }
+}
+
+#[test]
+fn aovs_are_written_next_to_the_image() {
+    let files = render_files(
+        "aovs",
+        &[
+            "--threads",
+            "2",
+            "--spp",
+            "2",
+            "--aov",
+            "depth,normal,object-id",
+        ],
+    );
+    let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "image.pfm",
+            "image_depth.pfm",
+            "image_normal.pfm",
+            "image_object_id.pfm"
+        ]
+    );
+    for (name, image) in &files {
+        assert_eq!(pixels(image).0, (48, 30), "{}", name);
+    }
+}
+
+#[test]
+fn time_limit_ends_the_render() {
+    let start = Instant::now();
+    let image = render(
+        "time_limit",
+        &["--threads", "2", "--spp", "1000000", "--time-limit", "1s"],
+    );
+    assert!(start.elapsed() < Duration::from_secs(60));
+    let (_, values) = pixels(&image);
+    assert!(values.iter().all(|value| value.is_finite()));
+    assert!(values.iter().any(|&value| value > 0.0));
+}
+
+#[test]
+fn stats_are_written_only_when_asked() {
+    let plain = render_files("no_stats", &["--threads", "2", "--spp", "4"]);
+    assert_eq!(plain.len(), 1);
+
+    let files = render_files("stats", &["--threads", "2", "--spp", "4", "--stats"]);
+    let (name, report) = &files[1];
+    assert_eq!(name, "image_stats.json");
+    let report: serde_json::Value = serde_json::from_slice(report).unwrap();
+    assert_eq!(report["primary_rays"], 48 * 30 * 4);
+}
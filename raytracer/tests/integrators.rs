@@ -0,0 +1,29 @@
+mod common;
+
+use common::{pixels, render};
+
+// the mean of all channels of a .pfm
+fn mean(image: &[u8]) -> f64 {
+    let (_, values) = pixels(image);
+    assert!(values.iter().all(|value| value.is_finite()));
+    values.iter().map(|&value| value as f64).sum::<f64>() / values.len() as f64
+}
+
+// All integrators solve the same light transport, so they agree on the Cornell box up
+// to their noise. PSSMLT scales its image by the brightness of its bootstrap samples,
+// which leaves its mean the noisiest.
+#[test]
+fn integrators_agree_on_the_cornell_box() {
+    let args = |integrator| ["--threads", "2", "--spp", "128", "--integrator", integrator];
+    let path = mean(&render("path", &args("path")));
+    for (integrator, tolerance) in [("bdpt", 0.05), ("sppm", 0.05), ("pssmlt", 0.1)] {
+        let other = mean(&render(integrator, &args(integrator)));
+        assert!(
+            (other / path - 1.0).abs() < tolerance,
+            "{} renders a mean of {} against {} of path",
+            integrator,
+            other,
+            path
+        );
+    }
+}